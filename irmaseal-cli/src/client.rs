@@ -61,4 +61,22 @@ impl<'a> Client<'a> {
             .json::<KeyResponse>()
             .await
     }
+
+    pub async fn sign_result(
+        &self,
+        token: &str,
+        timestamp: u64,
+    ) -> Result<SigningKeyResponse, ClientError> {
+        self.client
+            .get(
+                self.create_url("v1/sign/")
+                    .join(&format!("{}/{}", token, timestamp))
+                    .unwrap(),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<SigningKeyResponse>()
+            .await
+    }
 }
@@ -0,0 +1,100 @@
+//! Conversion between UNIX timestamps and dates in UTC, as given on the command line.
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The number of days since 1970-01-01 of the date in the proleptic Gregorian calendar.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// The year, month and day of the number of days since 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Parse a date in UTC as either `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM` or `YYYY-MM-DDTHH:MM:SS`.
+///
+/// Yields nothing when the date is malformed or lies before 1970.
+pub(crate) fn parse(s: &str) -> Option<u64> {
+    let (date, time) = match s.find(&['T', ' '][..]) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+
+    let number = |s: &str, len: usize| -> Option<u64> {
+        if s.len() == len && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    };
+
+    let mut date = date.split('-');
+    let year = number(date.next()?, 4)?;
+    let month = number(date.next()?, 2)?;
+    let day = number(date.next()?, 2)?;
+    if date.next().is_some() || year < 1970 || !(1..=12).contains(&month) {
+        return None;
+    }
+
+    let days_in_month = match month {
+        2 => days_from_civil(year, 3, 1) - days_from_civil(year, 2, 1),
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    let secs = match time {
+        None => 0,
+        Some(time) => {
+            let mut time = time.split(':');
+            let hours = number(time.next()?, 2)?;
+            let minutes = number(time.next()?, 2)?;
+            let seconds = match time.next() {
+                Some(seconds) => number(seconds, 2)?,
+                None => 0,
+            };
+            if time.next().is_some() || hours > 23 || minutes > 59 || seconds > 59 {
+                return None;
+            }
+
+            hours * 3600 + minutes * 60 + seconds
+        }
+    };
+
+    Some(days_from_civil(year, month, day) * SECS_PER_DAY + secs)
+}
+
+/// Format the timestamp as a date in UTC, such as `2021-01-31 12:00:00 UTC`.
+pub(crate) fn format(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days(timestamp / SECS_PER_DAY);
+    let secs = timestamp % SECS_PER_DAY;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -1,13 +1,21 @@
+use arrayvec::ArrayString;
 use clap::ArgMatches;
 use irmaseal_core::api::*;
-use irmaseal_core::stream::OpenerSealed;
+use irmaseal_core::armor::{is_armored, ArmorReader};
+use irmaseal_core::stream::{OpenerSealed, OpenerUnsealed, UnlockMethod};
+use irmaseal_core::util::{IoReader, IoWriter};
+use irmaseal_core::{Error, Identity, Metadata, Readable, UserSecretKey};
 
+use std::fs::OpenOptions;
+use std::io::{BufRead, ErrorKind};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::time::delay_for;
 
 use crate::client::{Client, ClientError, OwnedKeyChallenge};
+use crate::escrow::read_secret_key;
 
-fn print_qr(s: &str) {
+pub(crate) fn print_qr(s: &str) {
     let code = qrcode::QrCode::new(s).unwrap();
     let scode = code
         .render::<char>()
@@ -19,7 +27,7 @@ fn print_qr(s: &str) {
 }
 
 async fn wait_on_session(
-    client: Client<'_>,
+    client: &Client<'_>,
     sp: &OwnedKeyChallenge,
     timestamp: u64,
 ) -> Result<Option<KeyResponse>, ClientError> {
@@ -36,25 +44,108 @@ async fn wait_on_session(
     Ok(None)
 }
 
-pub async fn exec(m: &ArgMatches<'_>) {
-    let input = m.value_of("INPUT").unwrap();
-    let output = m.value_of("OUTPUT").unwrap();
-    let server = m.value_of("server").unwrap();
+/// Open the input file, which is either a binary or an armored bytestream.
+pub(crate) fn open(input: &str) -> Box<dyn Readable> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(input).unwrap());
 
-    eprintln!("Opening {}", input);
+    // Armored input is recognised by its BEGIN line, which can be preceded by other text.
+    if is_armored(file.fill_buf().unwrap()) {
+        Box::new(ArmorReader::new(IoReader::new(file)).unwrap())
+    } else {
+        Box::new(IoReader::new(file))
+    }
+}
+
+/// Let the user choose one of the identities, of which the attribute types are listed on stderr.
+fn choose_identity(identities: &[Identity]) -> Option<Identity> {
+    eprintln!("Several recipients match, please choose the attribute type to decrypt as:");
+    for (n, identity) in identities.iter().enumerate() {
+        eprintln!("{}) {}", n + 1, identity.attribute.atype);
+    }
+
+    let mut choice = String::new();
+    std::io::stdin().read_line(&mut choice).unwrap();
 
-    let r = crate::util::FileReader::new(std::fs::File::open(input).unwrap());
+    let identity = choice
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|n| identities.get(n).cloned());
+    if identity.is_none() {
+        eprintln!("{} is not one of the choices", choice.trim());
+    }
+
+    identity
+}
 
-    let (identity, o) = OpenerSealed::new(r).unwrap();
-    let timestamp = identity.timestamp;
+/// Select the recipient with the attribute value, or otherwise the first recipient.
+///
+/// The identity of a hidden recipient is only complete once its attribute value is filled in.
+/// As hidden recipients are recognised by a short hint, the value may match recipients of
+/// several attribute types, in which case the user chooses one of them. Recipients that match
+/// the same identity are all tried when unsealing.
+pub(crate) fn select_identity<R: Readable>(
+    o: &OpenerSealed<R>,
+    value: Option<&str>,
+    input: &str,
+) -> Option<Identity> {
+    let value = match value {
+        Some(value) => value,
+        None => {
+            let identity = o
+                .recipients()
+                .first()
+                .filter(|r| !r.is_hidden())
+                .map(|r| r.identity().clone());
+            if identity.is_none() {
+                eprintln!(
+                    "The recipients of {} are hidden, please specify --recipient or --passphrase",
+                    input
+                );
+            }
+
+            return identity;
+        }
+    };
 
-    let client = Client::new(server).unwrap();
+    let mut identities: Vec<Identity> = vec![];
+    for r in o.recipients() {
+        let mut identity = r.identity().clone();
+        identity.attribute.value = ArrayString::from(value).ok();
+        if r.matches(&identity) && !identities.contains(&identity) {
+            identities.push(identity);
+        }
+    }
+
+    match identities.len() {
+        0 => {
+            eprintln!("{} is not a recipient of {}", value, input);
+            None
+        }
+        1 => identities.pop(),
+        _ => choose_identity(&identities),
+    }
+}
+
+/// Request the private key of the identity from the PKG, once it has been disclosed with IRMA.
+///
+/// The PKG does not issue the key before the timestamp of the identity, which is when the stream is released.
+pub(crate) async fn request_key(client: &Client<'_>, identity: &Identity) -> Option<UserSecretKey> {
+    if identity.timestamp > crate::encrypt::now() {
+        eprintln!(
+            "The private key for {:#?} is only available from {}",
+            identity.attribute,
+            crate::date::format(identity.timestamp)
+        );
+        return None;
+    }
 
     eprintln!("Requesting private key for {:#?}", identity.attribute);
 
     let sp: OwnedKeyChallenge = client
         .request(&KeyRequest {
-            attribute: identity.attribute,
+            attribute: identity.attribute.clone(),
         })
         .await
         .unwrap();
@@ -63,16 +154,200 @@ pub async fn exec(m: &ArgMatches<'_>) {
 
     print_qr(&sp.qr);
 
-    if let Some(r) = wait_on_session(client, &sp, timestamp).await.unwrap() {
-        eprintln!("Disclosure successful, decrypting {} to {}", input, output);
+    match wait_on_session(client, &sp, identity.timestamp)
+        .await
+        .unwrap()
+    {
+        Some(r) => r.key,
+        None => {
+            eprintln!("Did not scan the QR code and disclose in time");
+            None
+        }
+    }
+}
+
+/// Unseal the stream for the identity, or yield nothing if the private key does not match.
+pub(crate) fn unseal<R: Readable>(
+    o: OpenerSealed<R>,
+    identity: &Identity,
+    usk: &UserSecretKey,
+    input: &str,
+    server: &str,
+) -> Option<OpenerUnsealed<R>> {
+    match o.unseal(identity, usk) {
+        Ok(o) => Some(o),
+        Err(Error::WrongKey) => {
+            eprintln!(
+                "The private key does not decrypt {}, it was not encrypted with the public key of {}",
+                input, server
+            );
+            None
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Unseal the stream using the passphrase it was encrypted with, which is prompted for.
+fn unseal_with_passphrase<R: Readable>(
+    o: OpenerSealed<R>,
+    input: &str,
+) -> Option<OpenerUnsealed<R>> {
+    if !o.unlock_methods().contains(&UnlockMethod::Passphrase) {
+        eprintln!("{} was not encrypted with a passphrase", input);
+        return None;
+    }
+
+    let passphrase = rpassword::prompt_password_stderr("Passphrase: ").unwrap();
+
+    match o.unseal_with_passphrase(&passphrase) {
+        Ok(o) => Some(o),
+        Err(Error::WrongKey) => {
+            eprintln!("The passphrase does not decrypt {}", input);
+            None
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Recover the stream using the escrow secret key of the organisation.
+fn unseal_with_escrow<R: Readable>(
+    o: OpenerSealed<R>,
+    path: &str,
+    input: &str,
+) -> Option<OpenerUnsealed<R>> {
+    if !o.unlock_methods().contains(&UnlockMethod::Escrow) {
+        eprintln!("{} was not encrypted for an escrow key", input);
+        return None;
+    }
+
+    let sk = read_secret_key(path)?;
+
+    match o.unseal_with_escrow(&sk) {
+        Ok(o) => Some(o),
+        Err(Error::WrongKey) => {
+            eprintln!("{} was encrypted for another escrow key", input);
+            None
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Restore the original file name next to the input, or otherwise strip the `.irma` or `.irma.asc` extension.
+fn default_output(input: &str, metadata: Option<&Metadata>) -> Option<PathBuf> {
+    let input = Path::new(input);
+
+    // Only accept plain file names, such that the output can not end up in another directory.
+    let file_name = metadata
+        .and_then(|m| m.file_name.as_ref())
+        .map(|name| Path::new(name.as_str()))
+        .filter(|name| name.file_name() == Some(name.as_os_str()));
+
+    let output = match file_name {
+        Some(file_name) => Some(input.with_file_name(file_name)),
+        None => input
+            .to_str()
+            .and_then(|input| {
+                input
+                    .strip_suffix(".irma.asc")
+                    .or_else(|| input.strip_suffix(".irma"))
+            })
+            .filter(|output| !output.is_empty())
+            .map(PathBuf::from),
+    };
+
+    // Never overwrite the input whilst reading it.
+    output.filter(|output| output != input)
+}
+
+pub async fn exec(m: &ArgMatches<'_>) {
+    let input = m.value_of("INPUT").unwrap();
+    let server = m.value_of("server").unwrap();
 
-        let mut o = o.unseal(&r.key.unwrap()).unwrap();
+    eprintln!("Opening {}", input);
 
-        let mut of = crate::util::FileWriter::new(std::fs::File::create(output).unwrap());
-        o.write_to(&mut of).unwrap();
+    let mut r = open(input);
+    let o = OpenerSealed::new(r.as_mut()).unwrap();
 
-        eprintln!("Succesfully decrypted {}", output);
+    let mut mvk = None;
+
+    let o = if m.is_present("passphrase") {
+        unseal_with_passphrase(o, input)
+    } else if let Some(path) = m.value_of("escrow") {
+        unseal_with_escrow(o, path, input)
     } else {
-        eprintln!("Did not scan the QR code and disclose in time");
+        let identity = match select_identity(&o, m.value_of("recipient"), input) {
+            Some(identity) => identity,
+            None => return,
+        };
+
+        let client = Client::new(server).unwrap();
+
+        // The verifying key of the PKG is only needed to tell who signed the stream.
+        if o.is_signed() {
+            mvk = client.parameters().await.unwrap().verifying_key;
+        }
+
+        match request_key(&client, &identity).await {
+            Some(usk) => unseal(o, &identity, &usk, input, server),
+            None => None,
+        }
+    };
+
+    let mut o = match o {
+        Some(o) => o,
+        None => return,
+    };
+
+    let output = match m.value_of("OUTPUT") {
+        Some(output) => PathBuf::from(output),
+        None => match default_output(input, o.metadata()) {
+            Some(output) => output,
+            None => {
+                eprintln!(
+                    "{} does not contain a file name, please specify OUTPUT",
+                    input
+                );
+                return;
+            }
+        },
     };
+
+    // Never overwrite an existing file, which may well be the original of a decrypted file.
+    let of = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&output)
+    {
+        Ok(of) => of,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            eprintln!(
+                "{} already exists, please specify another OUTPUT",
+                output.display()
+            );
+            return;
+        }
+        Err(e) => panic!("{}", e),
+    };
+
+    eprintln!("Decrypting {} to {}", input, output.display());
+
+    let mut of = IoWriter::new(of);
+    let written = o.write_to(&mut of).is_ok();
+
+    // The sender is only known once the signature trailing the stream has been verified.
+    let sender = mvk.as_ref().and_then(|mvk| o.sender(mvk)).cloned();
+
+    if written && o.validate() {
+        eprintln!("Succesfully decrypted {}", output.display());
+
+        if let Some(sender) = sender {
+            eprintln!("Signed by {:#?}", sender.attribute);
+        }
+    } else {
+        eprintln!(
+            "{} has been tampered with, {} is incomplete",
+            input,
+            output.display()
+        );
+    }
 }
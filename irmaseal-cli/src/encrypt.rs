@@ -1,69 +1,234 @@
+use arrayvec::ArrayString;
 use clap::ArgMatches;
-use irmaseal_core::stream::Sealer;
-use irmaseal_core::Identity;
-use std::time::SystemTime;
+use irmaseal_core::api::{KeyRequest, KeyStatus};
+use irmaseal_core::armor::ArmorWriter;
+use irmaseal_core::stream::{
+    Compression, Passphrase, SealOptions, Sealer, SenderKey, TimestampPolicy,
+};
+use irmaseal_core::util::IoWriter;
+use irmaseal_core::{Identity, Metadata, PublicKey, Writable};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::time::delay_for;
 
-fn now() -> u64 {
+use crate::client::Client;
+use crate::escrow::read_public_key;
+
+pub(crate) fn now() -> u64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs()
 }
 
-pub async fn exec(m: &ArgMatches<'_>) {
+/// Describe the input file, such that its name can be restored when decrypting.
+fn metadata(input: &str) -> Metadata {
+    let fs_metadata = std::fs::metadata(input).unwrap();
+
+    Metadata {
+        file_name: Path::new(input)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| ArrayString::from(name).ok()),
+        mime_type: None,
+        size: Some(fs_metadata.len()),
+        modified: fs_metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
+}
+
+/// Request a signing key for the sender, for which its identity is disclosed with IRMA.
+async fn request_signing_key(client: &Client<'_>, sender: &Identity) -> Option<SenderKey> {
+    let sp = client
+        .request(&KeyRequest {
+            attribute: sender.attribute.clone(),
+        })
+        .await
+        .unwrap();
+
+    eprintln!("Please scan the following QR-code with IRMA to sign:");
+
+    crate::decrypt::print_qr(&sp.qr);
+
+    for _ in 0..120 {
+        let r = client
+            .sign_result(&sp.token, sender.timestamp)
+            .await
+            .unwrap();
+
+        if r.status != KeyStatus::DoneValid {
+            delay_for(Duration::new(0, 500_000_000)).await;
+        } else {
+            return r.key;
+        }
+    }
+
+    None
+}
+
+/// Seal the input file, and yield the writer along with the number of bytes that were encrypted.
+fn seal<W: Writable>(
+    recipients: &[Identity],
+    options: &SealOptions,
+    pk: &PublicKey,
+    input: &str,
+    w: W,
+) -> (W, u64) {
     let mut rng = rand::thread_rng();
+    let mut sealer = Sealer::with_options(recipients, options, pk, &mut rng, w).unwrap();
+
+    let mut src = std::fs::File::open(input).unwrap();
+    let total_len = std::io::copy(&mut src, &mut sealer).unwrap();
+    let (w, _) = sealer.finish().unwrap();
+
+    (w, total_len)
+}
+
+/// The policy with which the timestamps of the recipients are rounded, as given by `--epoch`.
+pub(crate) fn timestamp_policy(m: &ArgMatches<'_>) -> TimestampPolicy {
+    match m.value_of("epoch") {
+        Some("day") => TimestampPolicy::Day,
+        Some("week") => TimestampPolicy::Week,
+        _ => TimestampPolicy::Exact,
+    }
+}
+
+/// The recipients with the e-mail addresses and BSNs that were given as arguments.
+pub(crate) fn recipients(m: &ArgMatches<'_>, timestamp: u64) -> Vec<Identity> {
+    let emails = m.values_of("email").into_iter().flatten();
+    let bsns = m.values_of("bsn").into_iter().flatten();
 
+    emails
+        .map(|email| Identity::new(timestamp, "pbdf.sidn-pbdf.email.email", Some(email)))
+        .chain(
+            bsns.map(|bsn| Identity::new(timestamp, "pbdf.gemeente.personalData.bsn", Some(bsn))),
+        )
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+pub async fn exec(m: &ArgMatches<'_>) {
     let input = m.value_of("INPUT").unwrap();
-    let email = m.value_of("email");
-    let bsn = m.value_of("bsn");
     let server = m.value_of("server").unwrap();
     let timestamp = now();
 
-    let i = match (email, bsn) {
-        (Some(email), None) => {
-            Identity::new(timestamp, "pbdf.sidn-pbdf.email.email", Some(email)).unwrap()
+    // The PKG only issues the keys of the recipients once their timestamp has passed, hence it is
+    // rounded up to the start of an epoch, as the sealer rounds down. Dates have four digit years,
+    // such that the start of the next epoch can always be represented.
+    let not_before = match m.value_of("not-before").map(crate::date::parse) {
+        Some(Some(not_before)) if not_before > timestamp => {
+            Some(timestamp_policy(m).round_up(not_before).unwrap())
         }
-        (None, Some(bsn)) => {
-            Identity::new(timestamp, "pbdf.gemeente.personalData.bsn", Some(bsn)).unwrap()
+        Some(Some(_)) => {
+            eprintln!("The date given to --not-before has already passed");
+            return;
         }
-        _ => {
-            eprintln!("Expected either email or BSN");
+        Some(None) => {
+            eprintln!("Expected a date such as 2030-01-31 or 2030-01-31T12:00 for --not-before");
             return;
         }
+        None => None,
     };
 
-    let client = crate::client::Client::new(server).unwrap();
+    let recipients = recipients(m, not_before.unwrap_or(timestamp));
+    if recipients.is_empty() {
+        eprintln!("Expected at least one email or BSN");
+        return;
+    }
+
+    let client = Client::new(server).unwrap();
 
     let parameters = client.parameters().await.unwrap();
     eprintln!("Fetched parameters from {}", server);
-    eprintln!("Encrypting for recipient {:#?}", i);
+    eprintln!("Encrypting for recipients {:#?}", recipients);
+    if let Some(not_before) = not_before {
+        eprintln!(
+            "The recipients can decrypt from {}",
+            crate::date::format(not_before)
+        );
+    }
 
-    let output = format!("{}.irma", input);
-    let mut w = crate::util::FileWriter::new(std::fs::File::create(&output).unwrap());
+    let sender = match m.value_of("sign") {
+        Some(email) => {
+            let sender =
+                Identity::new(timestamp, "pbdf.sidn-pbdf.email.email", Some(email)).unwrap();
+            match request_signing_key(&client, &sender).await {
+                Some(key) => Some(key),
+                None => {
+                    eprintln!("Did not scan the QR code and disclose in time");
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
 
-    let mut sealer = Sealer::new(&i, &parameters.public_key, &mut rng, &mut w).unwrap();
+    let passphrase = if m.is_present("passphrase") {
+        let passphrase = rpassword::prompt_password_stderr("Passphrase: ").unwrap();
+        if rpassword::prompt_password_stderr("Repeat passphrase: ").unwrap() != passphrase {
+            eprintln!("The passphrases do not match");
+            return;
+        }
+        Some(Passphrase::new(&passphrase))
+    } else {
+        None
+    };
 
-    use std::io::Read;
-    let mut src = std::fs::File::open(input).unwrap();
-    let mut buf = [0u8; 512];
+    let escrow = match m.value_of("escrow") {
+        Some(path) => match read_public_key(path) {
+            Some(key) => Some(key),
+            None => return,
+        },
+        None => None,
+    };
 
-    eprintln!("Encrypting {}...", input);
+    let armor = m.is_present("armor");
+    let output = if armor {
+        format!("{}.irma.asc", input)
+    } else {
+        format!("{}.irma", input)
+    };
+    let w = IoWriter::new(std::fs::File::create(&output).unwrap());
 
-    let mut total_len = 0;
-    loop {
-        let len = src.read(&mut buf).unwrap();
-        total_len += len;
+    let options = SealOptions {
+        metadata: Some(metadata(input)),
+        hide_values: m.is_present("hide"),
+        compression: if m.is_present("compress") {
+            Compression::Deflate
+        } else {
+            Compression::None
+        },
+        timestamp_policy: timestamp_policy(m),
+        sender,
+        passphrase,
+        escrow,
+        ..Default::default()
+    };
 
-        if len == 0 {
-            break;
-        }
+    eprintln!("Encrypting {}...", input);
 
-        use irmaseal_core::Writable;
-        sealer.write(&buf[0..len]).unwrap();
-    }
+    let pk = &parameters.public_key;
+    let total_len = if armor {
+        let (w, total_len) = seal(
+            &recipients,
+            &options,
+            pk,
+            input,
+            ArmorWriter::new(w).unwrap(),
+        );
+        w.finish().unwrap();
+        total_len
+    } else {
+        seal(&recipients, &options, pk, input, w).1
+    };
+
+    let sealed_len = std::fs::metadata(&output).unwrap().len();
 
     eprintln!(
-        "Encrypted {} bytes, written result to {}",
-        total_len, output
+        "Encrypted {} bytes, written {} bytes to {}",
+        total_len, sealed_len, output
     );
 }
@@ -0,0 +1,54 @@
+use clap::ArgMatches;
+use irmaseal_core::stream::{generate_escrow_keys, EscrowPublicKey, EscrowSecretKey};
+
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+fn write_owned<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) {
+    fn inner(path: &Path, contents: &[u8]) {
+        use std::io::Write;
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+            .unwrap()
+            .write_all(contents)
+            .unwrap()
+    }
+    inner(path.as_ref(), contents.as_ref())
+}
+
+/// Read the escrow public key of the organisation, or yield nothing if the file does not hold one.
+pub(crate) fn read_public_key(path: &str) -> Option<EscrowPublicKey> {
+    let key = EscrowPublicKey::from_bytes(&std::fs::read(path).unwrap());
+    if key.is_none() {
+        eprintln!("{} is not an escrow public key", path);
+    }
+
+    key
+}
+
+/// Read the escrow secret key of the organisation, or yield nothing if the file does not hold one.
+pub(crate) fn read_secret_key(path: &str) -> Option<EscrowSecretKey> {
+    let key = EscrowSecretKey::from_bytes(&std::fs::read(path).unwrap());
+    if key.is_none() {
+        eprintln!("{} is not an escrow secret key", path);
+    }
+
+    key
+}
+
+pub async fn exec(m: &ArgMatches<'_>) {
+    let mut rng = rand::thread_rng();
+    let (pk, sk) = generate_escrow_keys(&mut rng);
+
+    let public = m.value_of("public").unwrap();
+    let secret = m.value_of("secret").unwrap();
+
+    write_owned(public, pk.to_bytes());
+    write_owned(secret, &sk.to_bytes()[..]);
+
+    eprintln!("Written {} and {}", public, secret);
+}
@@ -1,7 +1,9 @@
 mod client;
+mod date;
 mod decrypt;
 mod encrypt;
-mod util;
+mod escrow;
+mod reseal;
 
 use clap::{load_yaml, App};
 use tokio::runtime::Runtime;
@@ -17,6 +19,10 @@ fn main() {
             crate::encrypt::exec(matches).await;
         } else if let Some(matches) = matches.subcommand_matches("decrypt") {
             crate::decrypt::exec(matches).await;
+        } else if let Some(matches) = matches.subcommand_matches("reseal") {
+            crate::reseal::exec(matches).await;
+        } else if let Some(matches) = matches.subcommand_matches("generate-escrow") {
+            crate::escrow::exec(matches).await;
         }
     });
 }
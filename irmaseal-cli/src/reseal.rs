@@ -0,0 +1,103 @@
+use clap::ArgMatches;
+use irmaseal_core::armor::ArmorWriter;
+use irmaseal_core::stream::{OpenerSealed, OpenerUnsealed, SealOptions, Sealer};
+use irmaseal_core::util::IoWriter;
+use irmaseal_core::{Error, Identity, PublicKey, Readable, Writable};
+
+use crate::client::Client;
+use crate::decrypt::{open, request_key, select_identity, unseal};
+use crate::encrypt::{now, recipients, timestamp_policy};
+use crate::escrow::read_public_key;
+
+/// Seal the unsealed stream again for the recipients, and yield the writer.
+fn reseal<R: Readable, W: Writable>(
+    o: OpenerUnsealed<R>,
+    recipients: &[Identity],
+    options: &SealOptions,
+    pk: &PublicKey,
+    w: W,
+) -> Result<(W, u64), Error> {
+    let mut rng = rand::thread_rng();
+    let sealer = Sealer::with_options(recipients, options, pk, &mut rng, w)?;
+
+    o.reseal(sealer)
+}
+
+pub async fn exec(m: &ArgMatches<'_>) {
+    let input = m.value_of("INPUT").unwrap();
+    let output = m.value_of("OUTPUT").unwrap();
+    let server = m.value_of("server").unwrap();
+
+    let recipients = recipients(m, now());
+    if recipients.is_empty() {
+        eprintln!("Expected at least one email or BSN");
+        return;
+    }
+
+    let escrow = match m.value_of("escrow") {
+        Some(path) => match read_public_key(path) {
+            Some(key) => Some(key),
+            None => return,
+        },
+        None => None,
+    };
+
+    eprintln!("Opening {}", input);
+
+    let mut r = open(input);
+    let o = OpenerSealed::new(r.as_mut()).unwrap();
+
+    let identity = match select_identity(&o, m.value_of("recipient"), input) {
+        Some(identity) => identity,
+        None => return,
+    };
+
+    let client = Client::new(server).unwrap();
+    let parameters = client.parameters().await.unwrap();
+
+    let usk = match request_key(&client, &identity).await {
+        Some(usk) => usk,
+        None => return,
+    };
+
+    let o = match unseal(o, &identity, &usk, input, server) {
+        Some(o) => o,
+        None => return,
+    };
+
+    eprintln!(
+        "Disclosure successful, encrypting {} for recipients {:#?}",
+        input, recipients
+    );
+
+    // The signature of the original sender does not carry over, as it covers the original header.
+    let options = SealOptions {
+        metadata: o.metadata().cloned(),
+        hide_values: m.is_present("hide"),
+        timestamp_policy: timestamp_policy(m),
+        escrow,
+        ..Default::default()
+    };
+
+    let pk = &parameters.public_key;
+    let w = IoWriter::new(std::fs::File::create(output).unwrap());
+    let result = if m.is_present("armor") {
+        reseal(o, &recipients, &options, pk, ArmorWriter::new(w).unwrap())
+            .and_then(|(w, _)| w.finish().map(|_| ()))
+    } else {
+        reseal(o, &recipients, &options, pk, w).map(|_| ())
+    };
+
+    // The output is never finished when the input turns out not to be authentic.
+    match result {
+        Ok(()) => {
+            let sealed_len = std::fs::metadata(output).unwrap().len();
+            eprintln!("Written {} bytes to {}", sealed_len, output);
+        }
+        Err(Error::IntegrityViolation) => {
+            std::fs::remove_file(output).unwrap();
+            eprintln!("{} has been tampered with, removed {}", input, output);
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
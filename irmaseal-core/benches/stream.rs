@@ -0,0 +1,124 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use irmaseal_core::stream::{OpenerSealed, ParallelSealer, SealOptions, Sealer};
+use irmaseal_core::util::SliceReader;
+use irmaseal_core::{Identity, PublicKey, UserSecretKey, Writable};
+use rand::RngCore;
+
+const CONTENT_SIZE: usize = 16 * 1024 * 1024;
+
+struct Props {
+    i: Identity,
+    pk: PublicKey,
+    usk: UserSecretKey,
+    content: Vec<u8>,
+}
+
+impl Props {
+    fn new() -> Props {
+        let mut rng = rand::thread_rng();
+        let i = Identity::new(
+            1566722350,
+            "pbdf.pbdf.email.email",
+            Some("bench@example.com"),
+        )
+        .unwrap();
+
+        let (pk, sk) = ibe::kiltz_vahlis_one::setup(&mut rng);
+        let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+
+        let mut content = vec![0u8; CONTENT_SIZE];
+        rng.fill_bytes(&mut content);
+
+        Props {
+            i,
+            pk: PublicKey::from(pk),
+            usk: UserSecretKey::from(usk),
+            content,
+        }
+    }
+
+    fn seal(&self) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let mut s = Sealer::new(&self.i, &self.pk, &mut rng, vec![]).unwrap();
+        s.write(&self.content).unwrap();
+        s.finish().unwrap().0
+    }
+
+    fn seal_parallel(&self, threads: usize) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let recipients = [self.i.clone()];
+        let options = SealOptions::default();
+        let mut s = ParallelSealer::new(&recipients, &options, threads, &self.pk, &mut rng, vec![])
+            .unwrap();
+        s.write(&self.content).unwrap();
+        s.finish().unwrap().0
+    }
+}
+
+fn thread_counts() -> Vec<usize> {
+    let max = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut res = vec![1];
+    while res[res.len() - 1] * 2 <= max {
+        res.push(res[res.len() - 1] * 2);
+    }
+    res
+}
+
+fn bench_seal(c: &mut Criterion) {
+    let props = Props::new();
+
+    let mut group = c.benchmark_group("seal");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(CONTENT_SIZE as u64));
+
+    group.bench_function("serial", |b| b.iter(|| props.seal()));
+    for threads in thread_counts() {
+        group.bench_with_input(
+            BenchmarkId::new("parallel", threads),
+            &threads,
+            |b, &threads| b.iter(|| props.seal_parallel(threads)),
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_open(c: &mut Criterion) {
+    let props = Props::new();
+    let sealed = props.seal();
+
+    let mut group = c.benchmark_group("open");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(CONTENT_SIZE as u64));
+
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            let o = OpenerSealed::new(SliceReader::new(&sealed)).unwrap();
+            let mut o = o.unseal(&props.i, &props.usk).unwrap();
+            let mut dst = Vec::with_capacity(CONTENT_SIZE);
+            o.write_to(&mut dst).unwrap();
+            assert!(o.validate());
+        })
+    });
+
+    for threads in thread_counts() {
+        group.bench_with_input(
+            BenchmarkId::new("parallel", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let o = OpenerSealed::new(SliceReader::new(&sealed)).unwrap();
+                    let mut o = o.unseal_parallel(&props.i, &props.usk, threads).unwrap();
+                    let mut dst = Vec::with_capacity(CONTENT_SIZE);
+                    o.write_to(&mut dst).unwrap();
+                    assert!(o.validate());
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_seal, bench_open);
+criterion_main!(benches);
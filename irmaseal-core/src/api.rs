@@ -1,5 +1,6 @@
 //! Structs that define the IRMAseal REST API protocol.
 
+use crate::kem::Scheme;
 use crate::*;
 use serde::{Deserialize, Serialize};
 
@@ -8,7 +9,14 @@ use serde::{Deserialize, Serialize};
 pub struct Parameters {
     pub format_version: u8,
     pub max_age: u64,
+    /// The KEM of the public key, which is Kiltz-Vahlis IBE1 if absent.
+    #[serde(default)]
+    pub scheme: Scheme,
     pub public_key: PublicKey,
+    /// The key with which the certificates of senders are verified, if the PKG issues signing keys.
+    #[cfg(feature = "signing")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifying_key: Option<crate::stream::MasterVerifyingKey>,
 }
 
 /// A request for the user secret key for an identity.
@@ -55,3 +63,14 @@ pub struct KeyResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<UserSecretKey>,
 }
+
+/// The response to the request for a signing key, with which a sender signs the streams it seals.
+#[cfg(feature = "signing")]
+#[derive(Serialize, Deserialize)]
+pub struct SigningKeyResponse {
+    /// The current status of the request.
+    pub status: KeyStatus,
+    /// The key will remain `None` until the status is `DoneValid`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<crate::stream::SenderKey>,
+}
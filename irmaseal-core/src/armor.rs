@@ -0,0 +1,404 @@
+//! ASCII armor for IRMAseal bytestreams, such that they can be pasted as text.
+//!
+//! The armored stream consists of a BEGIN line, the base64 encoded bytestream wrapped in lines
+//! of 64 characters, a line with the base64 encoded CRC-24 checksum prefixed by `=`, and an END line.
+
+use crate::*;
+
+const BEGIN: &[u8] = b"-----BEGIN IRMASEAL MESSAGE-----";
+const END: &[u8] = b"-----END IRMASEAL MESSAGE-----";
+
+/// The number of bytes that are encoded on a single line.
+const LINE_BYTES: usize = 48;
+/// The number of characters of a single encoded line.
+const LINE_CHARS: usize = LINE_BYTES / 3 * 4;
+/// The maximum length of a line that is read, excluding its line ending.
+const MAX_LINESIZE: usize = 76;
+/// The maximum number of bytes that a single line decodes to.
+const MAX_LINE_BYTES: usize = MAX_LINESIZE / 4 * 3;
+/// The maximum number of bytes that the `ArmorReader` yields at once.
+///
+/// Exceeds every header value that is read at once, as larger fields are rejected before they are read.
+pub const MAX_READSIZE: usize = 2048;
+
+const CRC24_INIT: u32 = 0x00B7_04CE;
+const CRC24_POLY: u32 = 0x0186_4CFB;
+
+/// Update the CRC-24 checksum as used by OpenPGP (RFC 4880) with the data.
+fn crc24(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc ^= u32::from(*b) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x0100_0000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+
+    crc & 0x00FF_FFFF
+}
+
+fn is_whitespace(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
+}
+
+fn trim(mut line: &[u8]) -> &[u8] {
+    while let Some((b, rest)) = line.split_first() {
+        if !is_whitespace(*b) {
+            break;
+        }
+        line = rest;
+    }
+
+    while let Some((b, rest)) = line.split_last() {
+        if !is_whitespace(*b) {
+            break;
+        }
+        line = rest;
+    }
+
+    line
+}
+
+/// Whether the start of a bytestream contains the BEGIN line of an armored IRMAseal bytestream.
+pub fn is_armored(buf: &[u8]) -> bool {
+    buf.windows(BEGIN.len()).any(|w| w == BEGIN)
+}
+
+/// Writer that encodes a bytestream as ASCII armor, such as the output of a `Sealer`.
+///
+/// The armor is only complete once `finish` has been called.
+pub struct ArmorWriter<W: Writable> {
+    buf: [u8; LINE_BYTES],
+    len: usize,
+    crc: u32,
+    w: W,
+}
+
+impl<W: Writable> ArmorWriter<W> {
+    /// Start the armor by writing the BEGIN line.
+    pub fn new(mut w: W) -> Result<ArmorWriter<W>, Error> {
+        w.write(BEGIN)?;
+        w.write(b"\n")?;
+
+        Ok(ArmorWriter {
+            buf: [0u8; LINE_BYTES],
+            len: 0,
+            crc: CRC24_INIT,
+            w,
+        })
+    }
+
+    /// Encode the buffered bytes as a single line, and write it.
+    fn write_line(&mut self) -> Result<(), Error> {
+        let mut line = [0u8; LINE_CHARS + 1];
+        let n = base64::encode_config_slice(&self.buf[..self.len], base64::STANDARD, &mut line);
+        line[n] = b'\n';
+
+        self.w.write(&line[..n + 1])?;
+        self.len = 0;
+
+        Ok(())
+    }
+
+    /// Write the remaining bytes, the checksum and the END line.
+    ///
+    /// Yields the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.len > 0 {
+            self.write_line()?;
+        }
+
+        let mut checksum = [0u8; 5];
+        checksum[0] = b'=';
+        base64::encode_config_slice(
+            &self.crc.to_be_bytes()[1..],
+            base64::STANDARD,
+            &mut checksum[1..],
+        );
+
+        self.w.write(&checksum)?;
+        self.w.write(b"\n")?;
+        self.w.write(END)?;
+        self.w.write(b"\n")?;
+
+        Ok(self.w)
+    }
+}
+
+impl<W: Writable> Writable for ArmorWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+        self.crc = crc24(self.crc, buf);
+
+        while !buf.is_empty() {
+            let n = core::cmp::min(LINE_BYTES - self.len, buf.len());
+            self.buf[self.len..self.len + n].copy_from_slice(&buf[..n]);
+            self.len += n;
+            buf = &buf[n..];
+
+            if self.len == LINE_BYTES {
+                self.write_line()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reader that decodes an armored bytestream, which can be passed to an `OpenerSealed`.
+///
+/// Any text before the BEGIN line is skipped. Throws an IntegrityViolation once the
+/// checksum does not match, and a FormatViolation if the armor is malformed.
+pub struct ArmorReader<R: Readable> {
+    line: [u8; MAX_LINESIZE],
+    buf: [u8; MAX_READSIZE + MAX_LINE_BYTES],
+    len: usize,
+    pos: usize,
+    crc: u32,
+    /// Whether a line with padding has been read, which has to be the final line of data.
+    padded: bool,
+    checksum: bool,
+    finished: bool,
+    r: R,
+}
+
+impl<R: Readable> ArmorReader<R> {
+    /// Skip to the BEGIN line of the armor.
+    ///
+    /// Throws a NotIRMASEAL error if the bytestream does not contain a BEGIN line.
+    pub fn new(r: R) -> Result<ArmorReader<R>, Error> {
+        let mut res = ArmorReader {
+            line: [0u8; MAX_LINESIZE],
+            buf: [0u8; MAX_READSIZE + MAX_LINE_BYTES],
+            len: 0,
+            pos: 0,
+            crc: CRC24_INIT,
+            padded: false,
+            checksum: false,
+            finished: false,
+            r,
+        };
+
+        loop {
+            match res.read_line() {
+                Ok(Some(len)) if trim(&res.line[..len]) == BEGIN => return Ok(res),
+                Ok(_) => {}
+                Err(Error::EndOfStream) => return Err(Error::NotIRMASEAL),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Yields the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Read a single line, and yield its length, or `None` if it is longer than `MAX_LINESIZE`.
+    fn read_line(&mut self) -> Result<Option<usize>, Error> {
+        let mut len = 0;
+        let mut overlong = false;
+
+        loop {
+            let b = match self.r.read_byte() {
+                Ok(b) => b,
+                Err(Error::EndOfStream) if len > 0 || overlong => break,
+                Err(e) => return Err(e),
+            };
+
+            if b == b'\n' {
+                break;
+            }
+
+            if len < MAX_LINESIZE {
+                self.line[len] = b;
+                len += 1;
+            } else if !is_whitespace(b) {
+                overlong = true;
+            }
+        }
+
+        Ok(if overlong { None } else { Some(len) })
+    }
+
+    /// Decode the next line of data into the buffer, verifying the checksum when it is reached.
+    fn next_line(&mut self) -> Result<(), Error> {
+        loop {
+            if self.finished {
+                return Err(Error::EndOfStream);
+            }
+
+            let len = match self.read_line() {
+                Ok(Some(len)) => len,
+                Ok(None) => return Err(Error::FormatViolation),
+                Err(Error::EndOfStream) => return Err(Error::PrematureEndError),
+                Err(e) => return Err(e),
+            };
+            let line = trim(&self.line[..len]);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == END {
+                if !self.checksum {
+                    return Err(Error::FormatViolation);
+                }
+
+                self.finished = true;
+                continue;
+            }
+
+            if self.checksum {
+                return Err(Error::FormatViolation);
+            }
+
+            if line[0] == b'=' {
+                let mut checksum = [0u8; 3];
+                if line.len() != 5
+                    || base64::decode_config_slice(&line[1..], base64::STANDARD, &mut checksum)
+                        != Ok(3)
+                {
+                    return Err(Error::FormatViolation);
+                }
+
+                if u32::from_be_bytes([0, checksum[0], checksum[1], checksum[2]]) != self.crc {
+                    return Err(Error::IntegrityViolation);
+                }
+
+                self.checksum = true;
+                continue;
+            }
+
+            if self.padded || !line.len().is_multiple_of(4) {
+                return Err(Error::FormatViolation);
+            }
+
+            self.padded = line.ends_with(b"=");
+            let n = base64::decode_config_slice(line, base64::STANDARD, &mut self.buf[self.len..])
+                .or(Err(Error::FormatViolation))?;
+            self.crc = crc24(self.crc, &self.buf[self.len..self.len + n]);
+            self.len += n;
+
+            return Ok(());
+        }
+    }
+}
+
+impl<R: Readable> Readable for ArmorReader<R> {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes_strict(1)?[0])
+    }
+
+    /// Yields at most `MAX_READSIZE` bytes at a time.
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        let n = core::cmp::min(n, MAX_READSIZE);
+
+        self.buf.copy_within(self.pos..self.len, 0);
+        self.len -= self.pos;
+        self.pos = 0;
+
+        while self.len < n {
+            match self.next_line() {
+                Ok(()) => {}
+                Err(Error::EndOfStream) if self.len > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let end = core::cmp::min(n, self.len);
+        let res = &self.buf[self.pos..end];
+        self.pos = end;
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::SliceReader;
+    use arrayvec::ArrayVec;
+
+    type ArmorBuf = ArrayVec<[u8; 4096]>;
+
+    fn armor(data: &[u8]) -> ArmorBuf {
+        let mut w = ArmorWriter::new(ArmorBuf::new()).unwrap();
+        w.write(data).unwrap();
+        w.finish().unwrap()
+    }
+
+    fn dearmor(armored: &[u8]) -> Result<ArmorBuf, Error> {
+        let mut r = ArmorReader::new(SliceReader::new(armored))?;
+        let mut res = ArmorBuf::new();
+
+        loop {
+            match r.read_bytes(100) {
+                Ok(buf) => res.try_extend_from_slice(buf).unwrap(),
+                Err(Error::EndOfStream) => return Ok(res),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn crc24_check() {
+        assert_eq!(crc24(CRC24_INIT, b"123456789"), 0x0021_CF02);
+    }
+
+    #[test]
+    fn eq_write_read() {
+        let mut data = [0u8; 1000];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i * 7) as u8;
+        }
+
+        for len in [0, 1, 2, 3, 47, 48, 49, 1000].iter() {
+            let armored = armor(&data[..*len]);
+
+            assert!(is_armored(&armored));
+            assert!(armored.starts_with(BEGIN));
+            assert!(armored
+                .split(|b| *b == b'\n')
+                .all(|l| l.len() <= LINE_CHARS));
+            assert_eq!(&data[..*len], dearmor(&armored).unwrap().as_slice());
+        }
+    }
+
+    #[test]
+    fn surrounding_text() {
+        let mut text = ArmorBuf::new();
+        text.try_extend_from_slice(b"Hi,\r\n\r\nsee below.\r\n\r\n")
+            .unwrap();
+        for line in armor(b"hello world").split(|b| *b == b'\n') {
+            text.try_extend_from_slice(line).unwrap();
+            text.try_extend_from_slice(b"\r\n").unwrap();
+        }
+        text.try_extend_from_slice(b"Regards").unwrap();
+
+        assert_eq!(b"hello world", dearmor(&text).unwrap().as_slice());
+    }
+
+    #[test]
+    fn corrupt() {
+        let armored = armor(b"hello world");
+        let begin = BEGIN.len() + 1;
+
+        let mut corrupted = armored.clone();
+        corrupted[begin] = if corrupted[begin] == b'A' { b'B' } else { b'A' };
+        assert!(matches!(
+            dearmor(&corrupted),
+            Err(Error::IntegrityViolation)
+        ));
+
+        let mut corrupted = armored.clone();
+        corrupted[begin] = b'*';
+        assert!(matches!(dearmor(&corrupted), Err(Error::FormatViolation)));
+
+        let truncated = &armored[..armored.len() - END.len() - 1];
+        assert!(matches!(dearmor(truncated), Err(Error::PrematureEndError)));
+
+        assert!(matches!(dearmor(b"no armor here"), Err(Error::NotIRMASEAL)));
+    }
+}
@@ -1,65 +1,116 @@
-use crate::util::open_ct;
+use crate::kem::{Artifact, Kem, KiltzVahlisOne};
+use crate::Identity;
+use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
 
 /// An IRMAseal public key for a system, as generated by the Private Key Generator (PKG).
-pub struct PublicKey(pub(crate) ibe::kiltz_vahlis_one::PublicKey);
+pub struct PublicKey<K: Kem = KiltzVahlisOne>(pub(crate) K::PublicKey);
+
+/// An IRMAseal master secret key, as held by the Private Key Generator (PKG).
+///
+/// Can not be cloned, and is overwritten with zeroes when dropped.
+pub struct SecretKey<K: Kem = KiltzVahlisOne>(K::SecretKey);
 
 /// An IRMAseal user private key, as generated by the Private Key Generator (PKG).
-pub struct UserSecretKey(pub(crate) ibe::kiltz_vahlis_one::UserSecretKey);
+///
+/// Is overwritten with zeroes when dropped.
+pub struct UserSecretKey<K: Kem = KiltzVahlisOne>(pub(crate) K::UserSecretKey);
 
-/// Serialize to a base64 encoded waters byte representation.
-impl Serialize for PublicKey {
+/// Serialize to a base64 encoded byte representation.
+impl<K: Kem> Serialize for PublicKey<K> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         serializer.serialize_str(&base64::encode(self.0.to_bytes().as_ref()))
     }
 }
 
-impl PublicKey {
+impl<K: Kem> PublicKey<K> {
+    pub fn to_bytes(&self) -> <K::PublicKey as Artifact>::Bytes {
+        self.0.to_bytes()
+    }
+
     pub fn from_base64(s: &str) -> Option<Self> {
-        let mut b = [0u8; 25056];
-        base64::decode_config_slice(s, base64::STANDARD, &mut b).ok()?;
-        let pk = open_ct(ibe::kiltz_vahlis_one::PublicKey::from_bytes(&b))?;
-        Some(PublicKey(pk))
+        let b = base64::decode(s).ok()?;
+        Some(PublicKey(K::PublicKey::from_bytes(&b)?))
     }
 }
 
-/// Deserialize from a base64 encoded waters byte representation.
-impl<'de> Deserialize<'de> for PublicKey {
+/// Deserialize from a base64 encoded byte representation.
+impl<'de, K: Kem> Deserialize<'de> for PublicKey<K> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let s = <&'de str>::deserialize(deserializer)?;
 
-        PublicKey::from_base64(s).ok_or_else(|| serde::de::Error::custom("Not a waters public key"))
+        PublicKey::from_base64(s).ok_or_else(|| serde::de::Error::custom("Not a valid public key"))
     }
 }
 
-/// Serialize to a base64 encoded waters byte representation.
-impl Serialize for UserSecretKey {
+impl<K: Kem> SecretKey<K> {
+    /// Generate the public key and the master secret key of a PKG.
+    pub fn setup<R: Rng + CryptoRng>(rng: &mut R) -> (PublicKey<K>, SecretKey<K>) {
+        let (pk, sk) = K::setup(rng);
+        (PublicKey(pk), SecretKey(sk))
+    }
+
+    /// Extract the user secret key for an identity.
+    pub fn extract_usk<R: Rng + CryptoRng>(
+        &self,
+        pk: &PublicKey<K>,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> UserSecretKey<K> {
+        UserSecretKey(K::extract_usk(&pk.0, &self.0, identity, rng))
+    }
+
+    /// The byte representation, which should be overwritten with zeroes after use.
+    pub fn to_bytes(&self) -> <K::SecretKey as Artifact>::Bytes {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        Some(SecretKey(K::SecretKey::from_bytes(b)?))
+    }
+}
+
+impl<K: Kem> Drop for SecretKey<K> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<K: Kem> Drop for UserSecretKey<K> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Serialize to a base64 encoded byte representation.
+impl<K: Kem> Serialize for UserSecretKey<K> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         serializer.serialize_str(&base64::encode(self.0.to_bytes().as_ref()))
     }
 }
 
-/// Deserialize from a base64 encoded waters byte representation.
-impl<'de> Deserialize<'de> for UserSecretKey {
+/// Deserialize from a base64 encoded byte representation.
+impl<'de, K: Kem> Deserialize<'de> for UserSecretKey<K> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let s = <&'de str>::deserialize(deserializer)?;
-        let mut b = [0u8; 192];
-        base64::decode_config_slice(s, base64::STANDARD, &mut b)
-            .map_err(serde::de::Error::custom)?;
-        let pk = open_ct(ibe::kiltz_vahlis_one::UserSecretKey::from_bytes(&b))
-            .ok_or_else(|| serde::de::Error::custom("Not a waters user secret key"))?;
-        Ok(UserSecretKey(pk))
+        let mut b = base64::decode(s).map_err(serde::de::Error::custom)?;
+        let usk = K::UserSecretKey::from_bytes(&b);
+        b.as_mut_slice().zeroize();
+
+        let usk = usk.ok_or_else(|| serde::de::Error::custom("Not a valid user secret key"))?;
+        Ok(UserSecretKey(usk))
     }
 }
 
-impl From<ibe::kiltz_vahlis_one::PublicKey> for PublicKey {
+impl From<ibe::kiltz_vahlis_one::PublicKey> for PublicKey<KiltzVahlisOne> {
     fn from(pk: ibe::kiltz_vahlis_one::PublicKey) -> Self {
         Self(pk)
     }
 }
 
-impl From<ibe::kiltz_vahlis_one::UserSecretKey> for UserSecretKey {
+impl From<ibe::kiltz_vahlis_one::UserSecretKey> for UserSecretKey<KiltzVahlisOne> {
     fn from(usk: ibe::kiltz_vahlis_one::UserSecretKey) -> Self {
-        Self(usk)
+        Self(usk.into())
     }
 }
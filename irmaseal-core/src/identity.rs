@@ -10,7 +10,7 @@ const IDENTITY_UNSET: u8 = 0xFF;
 type IdentityBuf = ArrayVec<[u8; 1024]>;
 
 /// An IRMAseal Attribute, which is a simple case of an IRMA ConDisCon.
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Attribute {
     #[serde(rename = "type")]
     pub atype: ArrayString<[u8; 255]>,
@@ -18,7 +18,7 @@ pub struct Attribute {
 }
 
 /// An IRMAseal identity, from which internally a Waters identity can be derived.
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Identity {
     pub timestamp: u64,
     pub attribute: Attribute,
@@ -31,7 +31,7 @@ impl Attribute {
     pub fn new(atype: &str, value: Option<&str>) -> Result<Self, Error> {
         let atype = ArrayString::<[u8; 255]>::from(atype).or(Err(Error::ConstraintViolation))?;
         let value = value
-            .map(|v| Ok(ArrayString::<[u8; 254]>::from(v).or(Err(Error::ConstraintViolation))?))
+            .map(|v| ArrayString::<[u8; 254]>::from(v).or(Err(Error::ConstraintViolation)))
             .transpose()?;
 
         Ok(Attribute { atype, value })
@@ -70,7 +70,8 @@ impl Attribute {
     pub fn read_from<R: Readable>(r: &mut R) -> Result<Self, Error> {
         let at_len = u8::from_be(r.read_byte()?);
         let at_len = usize::from(at_len);
-        let atype = core::str::from_utf8(r.read_bytes(at_len)?).or(Err(Error::FormatViolation))?;
+        let atype =
+            core::str::from_utf8(r.read_bytes_strict(at_len)?).or(Err(Error::FormatViolation))?;
 
         // Unwrap is valid because it impossible to not fit given u8.
         let atype = ArrayString::<[u8; 255]>::from(atype).unwrap();
@@ -80,8 +81,8 @@ impl Attribute {
             None
         } else {
             let i_len = usize::from(i_len);
-            let value =
-                core::str::from_utf8(r.read_bytes(i_len)?).or(Err(Error::FormatViolation))?;
+            let value = core::str::from_utf8(r.read_bytes_strict(i_len)?)
+                .or(Err(Error::FormatViolation))?;
 
             // Unwrap is valid because it impossible to not fit given u8.
             let value = ArrayString::<[u8; 254]>::from(value).unwrap();
@@ -111,7 +112,7 @@ impl Identity {
 
     /// Construct an identity from a bytestream.
     pub fn read_from<R: Readable>(r: &mut R) -> Result<Identity, Error> {
-        let timestamp = r.read_bytes(8)?;
+        let timestamp = r.read_bytes_strict(8)?;
         let timestamp = u64::from_be_bytes(*array_ref![timestamp, 0, 8]);
 
         Ok(Identity {
@@ -0,0 +1,204 @@
+//! Abstraction over the identity based key encapsulation mechanism (KEM) with which
+//! the body key of an IRMAseal bytestream is sealed for every recipient.
+
+use crate::*;
+use ibe::kiltz_vahlis_one as kvo;
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+const SCHEME_KILTZ_VAHLIS_ONE: u8 = 0x01;
+
+/// Identifier of an identity based KEM, as recorded in the header and in the PKG parameters.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Scheme {
+    /// The Kiltz-Vahlis IBE1 scheme, which is the only scheme of format version 0 streams.
+    #[default]
+    KiltzVahlisOne,
+}
+
+impl Scheme {
+    /// The byte representation of the scheme, as written to the header.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Scheme::KiltzVahlisOne => SCHEME_KILTZ_VAHLIS_ONE,
+        }
+    }
+
+    /// Throws an IncorrectVersion error for schemes that are not supported.
+    pub fn from_byte(b: u8) -> Result<Scheme, Error> {
+        match b {
+            SCHEME_KILTZ_VAHLIS_ONE => Ok(Scheme::KiltzVahlisOne),
+            _ => Err(Error::IncorrectVersion),
+        }
+    }
+}
+
+/// An artifact of a KEM, such as a key or a ciphertext, which has a byte representation of a fixed size.
+pub trait Artifact: Sized {
+    /// The size of the byte representation.
+    const SIZE: usize;
+
+    type Bytes: AsRef<[u8]>;
+
+    fn to_bytes(&self) -> Self::Bytes;
+
+    /// Yields `None` if the bytes are not of length `SIZE`, or do not represent a valid artifact.
+    fn from_bytes(b: &[u8]) -> Option<Self>;
+}
+
+/// An identity based key encapsulation mechanism.
+///
+/// The shared secret is encapsulated for an identity using the public key of the PKG,
+/// and can only be decapsulated using the user secret key of that identity.
+pub trait Kem {
+    /// The identifier of the scheme.
+    const SCHEME: Scheme;
+
+    type PublicKey: Artifact;
+    /// Is overwritten with zeroes once it is no longer used.
+    type SecretKey: Artifact + Zeroize;
+    /// Is overwritten with zeroes once it is no longer used.
+    type UserSecretKey: Artifact + Zeroize;
+    type Ciphertext: Artifact;
+    type SharedSecret: AsRef<[u8]> + Zeroize;
+
+    /// Generate the public key and the master secret key of a PKG.
+    fn setup<R: Rng + CryptoRng>(rng: &mut R) -> (Self::PublicKey, Self::SecretKey);
+
+    /// Extract the user secret key for an identity, using the master secret key.
+    fn extract_usk<R: Rng + CryptoRng>(
+        pk: &Self::PublicKey,
+        sk: &Self::SecretKey,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> Self::UserSecretKey;
+
+    /// Generate a shared secret, and encapsulate it for an identity.
+    fn encaps<R: Rng + CryptoRng>(
+        pk: &Self::PublicKey,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> (Self::Ciphertext, Self::SharedSecret);
+
+    /// Decapsulate the shared secret, which is only the original one if the identities match.
+    fn decaps(usk: &Self::UserSecretKey, c: &Self::Ciphertext) -> Self::SharedSecret;
+}
+
+/// The Kiltz-Vahlis IBE1 scheme, as implemented by `ibe::kiltz_vahlis_one`.
+pub struct KiltzVahlisOne;
+
+macro_rules! impl_artifact {
+    ($type: ty, $size: expr) => {
+        impl Artifact for $type {
+            const SIZE: usize = $size;
+
+            type Bytes = [u8; $size];
+
+            fn to_bytes(&self) -> [u8; $size] {
+                <$type>::to_bytes(self)
+            }
+
+            fn from_bytes(b: &[u8]) -> Option<Self> {
+                if b.len() != $size {
+                    return None;
+                }
+
+                crate::util::open_ct(<$type>::from_bytes(arrayref::array_ref![b, 0, $size]))
+            }
+        }
+    };
+}
+
+impl_artifact!(kvo::PublicKey, 25056);
+impl_artifact!(kvo::SecretKey, 48);
+impl_artifact!(kvo::UserSecretKey, 192);
+impl_artifact!(kvo::CipherText, 144);
+
+macro_rules! impl_secret {
+    ($name: ident, $type: ty, $size: expr) => {
+        /// Owns a secret of the Kiltz-Vahlis IBE1 scheme in its byte representation, which is only
+        /// decoded for the duration of a single operation, such that it can be zeroized safely.
+        ///
+        /// Unlike the secret itself it is not `Copy`, hence it is never copied implicitly.
+        pub struct $name([u8; $size]);
+
+        impl $name {
+            fn new(secret: &$type) -> Self {
+                $name(secret.to_bytes())
+            }
+
+            fn decode(&self) -> $type {
+                // The bytes were encoded from a valid secret.
+                <$type as Artifact>::from_bytes(&self.0).unwrap()
+            }
+        }
+
+        impl Artifact for $name {
+            const SIZE: usize = $size;
+
+            type Bytes = [u8; $size];
+
+            fn to_bytes(&self) -> [u8; $size] {
+                self.0
+            }
+
+            fn from_bytes(b: &[u8]) -> Option<Self> {
+                <$type as Artifact>::from_bytes(b).map(|secret| $name::new(&secret))
+            }
+        }
+
+        impl Zeroize for $name {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+            }
+        }
+    };
+}
+
+impl_secret!(KvoSecretKey, kvo::SecretKey, 48);
+impl_secret!(KvoUserSecretKey, kvo::UserSecretKey, 192);
+
+impl From<kvo::UserSecretKey> for KvoUserSecretKey {
+    fn from(usk: kvo::UserSecretKey) -> Self {
+        KvoUserSecretKey::new(&usk)
+    }
+}
+
+impl Kem for KiltzVahlisOne {
+    const SCHEME: Scheme = Scheme::KiltzVahlisOne;
+
+    type PublicKey = kvo::PublicKey;
+    type SecretKey = KvoSecretKey;
+    type UserSecretKey = KvoUserSecretKey;
+    type Ciphertext = kvo::CipherText;
+    type SharedSecret = [u8; 288];
+
+    fn setup<R: Rng + CryptoRng>(rng: &mut R) -> (kvo::PublicKey, KvoSecretKey) {
+        let (pk, sk) = kvo::setup(rng);
+        (pk, KvoSecretKey::new(&sk))
+    }
+
+    fn extract_usk<R: Rng + CryptoRng>(
+        pk: &kvo::PublicKey,
+        sk: &KvoSecretKey,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> KvoUserSecretKey {
+        KvoUserSecretKey::new(&kvo::extract_usk(pk, &sk.decode(), &identity.derive(), rng))
+    }
+
+    fn encaps<R: Rng + CryptoRng>(
+        pk: &kvo::PublicKey,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> (kvo::CipherText, [u8; 288]) {
+        let (c, k) = kvo::encrypt(pk, &identity.derive(), rng);
+        (c, k.to_bytes())
+    }
+
+    fn decaps(usk: &KvoUserSecretKey, c: &kvo::CipherText) -> [u8; 288] {
+        kvo::decrypt(&usk.decode(), c).to_bytes()
+    }
+}
@@ -1,9 +1,12 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 mod artifacts;
 mod identity;
+mod metadata;
 
 pub mod api;
+pub mod armor;
+pub mod kem;
 pub mod util;
 
 #[cfg(feature = "stream")]
@@ -11,6 +14,7 @@ pub mod stream;
 
 pub use artifacts::*;
 pub use identity::*;
+pub use metadata::*;
 
 #[derive(Debug)]
 pub enum Error {
@@ -21,6 +25,62 @@ pub enum Error {
     UpstreamWritableError,
     EndOfStream,
     PrematureEndError,
+    UnknownRecipient,
+    IncorrectScheme,
+    IntegrityViolation,
+    WrongKey,
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::NotIRMASEAL => write!(f, "not an IRMAseal bytestream"),
+            Error::IncorrectVersion => write!(f, "unsupported format version"),
+            Error::ConstraintViolation => write!(f, "constraint violation"),
+            Error::FormatViolation => write!(f, "format violation"),
+            Error::UpstreamWritableError => write!(f, "upstream writable error"),
+            Error::EndOfStream => write!(f, "end of stream"),
+            Error::PrematureEndError => write!(f, "premature end of stream"),
+            Error::UnknownRecipient => write!(f, "unknown recipient"),
+            Error::IncorrectScheme => write!(f, "key does not match the scheme of the bytestream"),
+            Error::IntegrityViolation => write!(f, "integrity violation"),
+            Error::WrongKey => write!(f, "key does not unseal the bytestream"),
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::EndOfStream | Error::PrematureEndError => {
+                std::io::Error::new(std::io::ErrorKind::UnexpectedEof, e)
+            }
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
 }
 
 /// A writable resource that accepts chunks of a bytestream.
@@ -29,6 +89,12 @@ pub trait Writable {
     fn write(&mut self, buf: &[u8]) -> Result<(), Error>;
 }
 
+impl<W: Writable + ?Sized> Writable for &mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        (**self).write(buf)
+    }
+}
+
 /// A readable resource that yields chunks of a bytestream.
 pub trait Readable {
     /// Read exactly one byte. Will throw `Error::EndOfStream` if that byte
@@ -49,3 +115,13 @@ pub trait Readable {
         }
     }
 }
+
+impl<R: Readable + ?Sized> Readable for &mut R {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        (**self).read_byte()
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        (**self).read_bytes(n)
+    }
+}
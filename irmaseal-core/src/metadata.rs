@@ -0,0 +1,131 @@
+use super::{Error, Readable, Writable};
+use arrayref::array_ref;
+use arrayvec::{ArrayString, ArrayVec};
+use serde::{Deserialize, Serialize};
+
+const METADATA_FILE_NAME: u8 = 0x01;
+const METADATA_MIME_TYPE: u8 = 0x02;
+const METADATA_SIZE: u8 = 0x03;
+const METADATA_MODIFIED: u8 = 0x04;
+
+/// The maximum size of the byte representation of metadata.
+///
+/// Must be at least 2+255+2+255+2+8+2+8 = 534
+pub(crate) const MAX_METADATASIZE: usize = 1024;
+
+#[allow(dead_code)]
+pub(crate) type MetadataBuf = ArrayVec<[u8; MAX_METADATASIZE]>;
+
+/// Information about the content of an IRMAseal bytestream, which is sealed along with it.
+///
+/// All fields are optional. Fields that are not understood are skipped when reading.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// The original name of the file, without any directories.
+    pub file_name: Option<ArrayString<[u8; 255]>>,
+    /// The MIME type of the content, such as `application/pdf`.
+    pub mime_type: Option<ArrayString<[u8; 255]>>,
+    /// The length of the content in bytes.
+    pub size: Option<u64>,
+    /// The time of the last modification of the original file, in seconds since the UNIX epoch.
+    pub modified: Option<u64>,
+}
+
+fn write_entry<W: Writable>(w: &mut W, mtype: u8, value: &[u8]) -> Result<(), Error> {
+    use core::convert::TryFrom;
+
+    let len = u8::try_from(value.len()).or(Err(Error::ConstraintViolation))?;
+    w.write(&[mtype, len])?;
+    w.write(value)
+}
+
+fn read_str(value: &[u8]) -> Result<ArrayString<[u8; 255]>, Error> {
+    let value = core::str::from_utf8(value).or(Err(Error::FormatViolation))?;
+
+    ArrayString::<[u8; 255]>::from(value).or(Err(Error::FormatViolation))
+}
+
+fn read_u64(value: &[u8]) -> Result<u64, Error> {
+    if value.len() != 8 {
+        return Err(Error::FormatViolation);
+    }
+
+    Ok(u64::from_be_bytes(*array_ref![value, 0, 8]))
+}
+
+impl Metadata {
+    /// Write the byte representation of this metadata as a bytestream.
+    ///
+    /// Every field that is set is written as its type, its length and its value.
+    pub fn write_to<W: Writable>(&self, w: &mut W) -> Result<(), Error> {
+        if let Some(file_name) = &self.file_name {
+            write_entry(w, METADATA_FILE_NAME, file_name.as_bytes())?;
+        }
+
+        if let Some(mime_type) = &self.mime_type {
+            write_entry(w, METADATA_MIME_TYPE, mime_type.as_bytes())?;
+        }
+
+        if let Some(size) = self.size {
+            write_entry(w, METADATA_SIZE, &size.to_be_bytes())?;
+        }
+
+        if let Some(modified) = self.modified {
+            write_entry(w, METADATA_MODIFIED, &modified.to_be_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Construct metadata from a bytestream, which is read until it is exhausted.
+    pub fn read_from<R: Readable>(r: &mut R) -> Result<Metadata, Error> {
+        let mut res = Metadata::default();
+
+        loop {
+            let mtype = match r.read_byte() {
+                Ok(mtype) => mtype,
+                Err(Error::EndOfStream) => return Ok(res),
+                Err(e) => return Err(e),
+            };
+
+            let len = usize::from(r.read_byte()?);
+            let value: &[u8] = if len == 0 {
+                &[]
+            } else {
+                r.read_bytes_strict(len)?
+            };
+
+            match mtype {
+                METADATA_FILE_NAME => res.file_name = Some(read_str(value)?),
+                METADATA_MIME_TYPE => res.mime_type = Some(read_str(value)?),
+                METADATA_SIZE => res.size = Some(read_u64(value)?),
+                METADATA_MODIFIED => res.modified = Some(read_u64(value)?),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::SliceReader;
+
+    #[test]
+    fn eq_write_read() {
+        let mut buf = MetadataBuf::new();
+
+        let m = Metadata {
+            file_name: Some(ArrayString::from("report.pdf").unwrap()),
+            mime_type: Some(ArrayString::from("application/pdf").unwrap()),
+            size: Some(1566722350),
+            modified: None,
+        };
+        m.write_to(&mut buf).unwrap();
+
+        let mut reader = SliceReader::new(&buf);
+        let m2 = Metadata::read_from(&mut reader).unwrap();
+
+        assert_eq!(m, m2);
+    }
+}
@@ -0,0 +1,496 @@
+use crate::kem::Kem;
+use crate::stream::header::{BodyKeys, Header, HybridSecretKey};
+use crate::stream::opener::Body;
+use crate::stream::sealer::DEFAULT_OPTIONS;
+use crate::stream::*;
+use crate::util::SliceReader;
+use crate::*;
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures::future::poll_fn;
+use futures::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use futures::ready;
+use rand::{CryptoRng, Rng};
+use std::io;
+use std::vec::Vec;
+
+/// The number of bytes beyond a full segment that are buffered, which is the lookahead
+/// that determines whether the segment is the final one.
+#[cfg(not(feature = "signing"))]
+const LOOKAHEAD: usize = 1;
+/// Also suffices for the signature that trails a signed stream, which is held back.
+#[cfg(feature = "signing")]
+const LOOKAHEAD: usize = 1 + SIGNATURE_SIZE;
+
+/// The number of bytes that are buffered before the body is opened any further.
+///
+/// Suffices for a full segment of the block size in the header plus its lookahead,
+/// and for a full block of a format version 0 stream.
+fn readahead(keys: &BodyKeys) -> usize {
+    let segment_size = match keys {
+        BodyKeys::V0(..) => SEGMENTSIZE,
+        BodyKeys::V2(_, format, _) => format.segment_size(),
+    };
+
+    segment_size + LOOKAHEAD
+}
+
+/// The number of bytes that are additionally requested whilst the header is incomplete.
+const HEADER_CHUNKSIZE: usize = 1024;
+
+/// Buffer in front of an `AsyncRead`, such that the blocking stream logic can operate on its contents.
+struct ReadBuffer<R: AsyncRead + Unpin> {
+    r: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> ReadBuffer<R> {
+    fn new(r: R) -> ReadBuffer<R> {
+        ReadBuffer {
+            r,
+            buf: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Read until at least `target` bytes are buffered, or the reader is exhausted.
+    fn poll_fill(&mut self, cx: &mut Context, target: usize) -> Poll<Result<(), Error>> {
+        while !self.eof && self.buf.len() < target {
+            let len = self.buf.len();
+            self.buf.resize(target, 0u8);
+
+            let res = Pin::new(&mut self.r).poll_read(cx, &mut self.buf[len..]);
+            match res {
+                Poll::Ready(Ok(n)) => {
+                    self.buf.truncate(len + n);
+                    self.eof = n == 0;
+                }
+                Poll::Ready(Err(e)) => {
+                    self.buf.truncate(len);
+                    return Poll::Ready(Err(e.into()));
+                }
+                Poll::Pending => {
+                    self.buf.truncate(len);
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    async fn fill(&mut self, target: usize) -> Result<(), Error> {
+        poll_fn(|cx| self.poll_fill(cx, target)).await
+    }
+
+    /// Discard the first `n` buffered bytes.
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+    }
+}
+
+/// Sealer for an asynchronous bytestream, which converts it into an IRMAseal encrypted bytestream.
+///
+/// Behaves like `Sealer`, except that the sealed stream is written to an `AsyncWrite`.
+/// The final segment is written by `finish`, or by closing the `AsyncSealer` as an `AsyncWrite`.
+pub struct AsyncSealer<'a, W: AsyncWrite + Unpin> {
+    /// Is `None` once the final segment has been sealed.
+    sealer: Option<Sealer<'a, Vec<u8>>>,
+    /// Sealed bytes that have yet to be written to `w`.
+    pending: Vec<u8>,
+    written: usize,
+    total: u64,
+    w: W,
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncSealer<'a, W> {
+    /// Seal an asynchronous bytestream for a single recipient.
+    ///
+    /// The header is only written to `w` once the first plaintext is written, or when finishing.
+    pub fn new<K: Kem, R: Rng + CryptoRng>(
+        i: &Identity,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<AsyncSealer<'a, W>, Error> {
+        Self::with_recipients(core::slice::from_ref(i), pk, rng, w)
+    }
+
+    /// Seal an asynchronous bytestream for several recipients at once.
+    ///
+    /// Throws a ConstraintViolation when there are no recipients, or more than `MAX_RECIPIENTS`.
+    pub fn with_recipients<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<AsyncSealer<'a, W>, Error> {
+        Self::with_options(recipients, &DEFAULT_OPTIONS, pk, rng, w)
+    }
+
+    /// Seal an asynchronous bytestream for several recipients at once, with optional properties such as metadata.
+    pub fn with_options<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        options: &'a SealOptions,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<AsyncSealer<'a, W>, Error> {
+        let sealer = Sealer::with_options(recipients, options, pk, rng, Vec::new())?;
+
+        Ok(AsyncSealer {
+            sealer: Some(sealer),
+            pending: Vec::new(),
+            written: 0,
+            total: 0,
+            w,
+        })
+    }
+
+    /// Seal the plaintext, and write the segments that are complete.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.seal(buf)?;
+        poll_fn(|cx| self.poll_drain(cx)).await
+    }
+
+    /// Seal and write the final segment, which marks the stream as complete.
+    ///
+    /// Yields the underlying writer, which is flushed but not closed,
+    /// and the total number of bytes written to it.
+    pub async fn finish(mut self) -> Result<(W, u64), Error> {
+        self.seal_final()?;
+        poll_fn(|cx| self.poll_drain(cx)).await?;
+        self.w.flush().await?;
+
+        Ok((self.w, self.total))
+    }
+
+    fn seal(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let sealer = self.sealer.as_mut().ok_or(Error::ConstraintViolation)?;
+        Writable::write(sealer, buf)?;
+        self.pending.append(sealer.writer_mut());
+
+        Ok(())
+    }
+
+    fn seal_final(&mut self) -> Result<(), Error> {
+        if let Some(sealer) = self.sealer.take() {
+            let (mut buf, total) = sealer.finish()?;
+            self.pending.append(&mut buf);
+            self.total = total;
+        }
+
+        Ok(())
+    }
+
+    /// Write all pending sealed bytes to the underlying writer.
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<Result<(), Error>> {
+        while self.written < self.pending.len() {
+            let n = ready!(Pin::new(&mut self.w).poll_write(cx, &self.pending[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+
+            self.written += n;
+        }
+
+        self.pending.clear();
+        self.written = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Closing the `AsyncSealer` seals the final segment, and closes the underlying writer.
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncSealer<'_, W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.seal(buf)?;
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.w).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.seal_final()?;
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.w).poll_close(cx)
+    }
+}
+
+/// First stage opener of an asynchronous IRMAseal encrypted bytestream.
+///
+/// Behaves like `OpenerSealed`, except that the stream is read from an `AsyncRead`.
+pub struct AsyncOpenerSealed<R: AsyncRead + Unpin> {
+    header: Header,
+    r: ReadBuffer<R>,
+}
+
+/// Second stage opener of an asynchronous IRMAseal encrypted bytestream.
+///
+/// For format version 2 streams, every segment is authenticated before it is yielded by `read`.
+///
+/// **Warning**: for format version 0 streams, will only validate the authenticity of the plaintext when calling `validate`.
+pub struct AsyncOpenerUnsealed<R: AsyncRead + Unpin> {
+    body: Body,
+    metadata: Option<Metadata>,
+    r: ReadBuffer<R>,
+    /// The number of bytes of the last plaintext chunk that were consumed through `AsyncRead`.
+    offset: usize,
+    /// The number of bytes that are buffered before the body is opened any further.
+    readahead: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncOpenerSealed<R> {
+    /// Starts interpreting an asynchronous bytestream as an IRMAseal stream.
+    /// Reads until the entire header is available, and lists the recipients for which the stream is intended.
+    pub async fn new(r: R) -> Result<AsyncOpenerSealed<R>, Error> {
+        let mut r = ReadBuffer::new(r);
+
+        loop {
+            let mut sr = SliceReader::new(&r.buf);
+
+            match Header::read_from(&mut sr) {
+                Ok((header, sr)) => {
+                    let consumed = r.buf.len() - sr.remaining();
+                    r.consume(consumed);
+
+                    return Ok(AsyncOpenerSealed { header, r });
+                }
+                Err(Error::EndOfStream) | Err(Error::PrematureEndError) if !r.eof => {
+                    let target = r.buf.len() + HEADER_CHUNKSIZE;
+                    r.fill(target).await?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The recipients for which the stream is intended.
+    pub fn recipients(&self) -> &[Recipient] {
+        &self.header.recipients
+    }
+
+    /// The earliest timestamp of the identities of the recipients, as a UNIX timestamp.
+    ///
+    /// The PKG only issues the key of an identity once its timestamp has passed. The header does
+    /// not record when the stream was sealed, hence this does not tell whether the stream was
+    /// sealed for a future timestamp.
+    pub fn earliest_timestamp(&self) -> u64 {
+        self.header.earliest_timestamp()
+    }
+
+    /// The policy with which the timestamps of the recipients are rounded.
+    pub fn timestamp_policy(&self) -> TimestampPolicy {
+        self.header.timestamp_policy
+    }
+
+    /// The ways in which the stream can be unsealed.
+    pub fn unlock_methods(&self) -> arrayvec::ArrayVec<[UnlockMethod; 3]> {
+        self.header.unlock_methods()
+    }
+
+    /// The escrow key of the organisation that can recover the stream, if any.
+    #[cfg(feature = "escrow")]
+    pub fn escrow(&self) -> Option<&EscrowPublicKey> {
+        self.header.escrow()
+    }
+
+    /// Whether the stream is signed by its sender, whose identity is yielded once the stream is read.
+    #[cfg(feature = "signing")]
+    pub fn is_signed(&self) -> bool {
+        self.header.sender.is_some()
+    }
+
+    /// Will unseal the stream continuation for one of the recipients and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when none of the `recipients` matches the identity,
+    /// an IncorrectScheme error when the stream was sealed using another KEM or in hybrid mode,
+    /// and a WrongKey error when `usk` is not the key of the identity.
+    pub async fn unseal<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        self.unseal_with(identity, usk, None).await
+    }
+
+    /// Will unseal the stream continuation for one of the recipients in hybrid mode.
+    ///
+    /// Throws an IncorrectScheme error when the stream was not sealed in hybrid mode.
+    #[cfg(feature = "hybrid")]
+    pub async fn unseal_hybrid<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hsk: &HybridSecretKey,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        self.unseal_with(identity, usk, Some(hsk)).await
+    }
+
+    async fn unseal_with<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        let AsyncOpenerSealed { header, r } = self;
+        let (keys, metadata) = header.unseal(identity, usk, hybrid)?;
+
+        AsyncOpenerUnsealed::new(keys, metadata, r).await
+    }
+
+    /// Will unseal the stream continuation using the passphrase, and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed for a passphrase,
+    /// and a WrongKey error when the passphrase is not correct.
+    #[cfg(feature = "passphrase")]
+    pub async fn unseal_with_passphrase(
+        self,
+        passphrase: &str,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        let AsyncOpenerSealed { header, r } = self;
+        let (keys, metadata) = header.unseal_passphrase(passphrase)?;
+
+        AsyncOpenerUnsealed::new(keys, metadata, r).await
+    }
+
+    /// Will unseal the stream continuation using the escrow secret key, and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed in escrow mode,
+    /// and a WrongKey error when it was sealed for another escrow key.
+    #[cfg(feature = "escrow")]
+    pub async fn unseal_with_escrow(
+        self,
+        sk: &EscrowSecretKey,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        let AsyncOpenerSealed { header, r } = self;
+        let (keys, metadata) = header.unseal_escrow(sk)?;
+
+        AsyncOpenerUnsealed::new(keys, metadata, r).await
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncOpenerUnsealed<R> {
+    async fn new(
+        keys: BodyKeys<'_>,
+        metadata: Option<Metadata>,
+        mut r: ReadBuffer<R>,
+    ) -> Result<AsyncOpenerUnsealed<R>, Error> {
+        let readahead = readahead(&keys);
+        r.fill(IVSIZE).await?;
+
+        let mut sr = SliceReader::new(&r.buf);
+        let body = Body::new(keys, &mut sr)?;
+        let consumed = r.buf.len() - sr.remaining();
+        r.consume(consumed);
+
+        Ok(AsyncOpenerUnsealed {
+            body,
+            metadata,
+            r,
+            offset: 0,
+            readahead,
+        })
+    }
+
+    /// The metadata that was sealed along with the content, if any.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Read up to a single block of plaintext at a time.
+    ///
+    /// Yields an empty slice for segments that only consist of padding.
+    /// Throws an IntegrityViolation if a segment is not authentic, or the stream has been truncated.
+    pub async fn read(&mut self) -> Result<&[u8], Error> {
+        self.r.fill(self.readahead).await?;
+        self.step()
+    }
+
+    /// Open the body as far as the buffered bytes allow.
+    fn step(&mut self) -> Result<&[u8], Error> {
+        let mut sr = SliceReader::new(&self.r.buf);
+        let res = self.body.read(&mut sr);
+        let consumed = self.r.buf.len() - sr.remaining();
+        self.r.consume(consumed);
+
+        res
+    }
+
+    /// Will check whether the entire stream was authentic once it is exhausted.
+    /// Will only yield the correct value once the **entire** stream is read
+    /// using `write_to`, or by manually calling `read` until `Error::EndOfStream` is yielded.
+    ///
+    /// Consumes the opener, hence the `sender` of a signed stream has to be queried beforehand.
+    pub fn validate(self) -> bool {
+        self.body.is_valid()
+    }
+
+    /// The identity of the sender that signed the stream, if the PKG with the verifying key certified it.
+    ///
+    /// Will only yield the sender once the **entire** stream is read, and before `validate`,
+    /// as for `OpenerUnsealed::sender`.
+    #[cfg(feature = "signing")]
+    pub fn sender(&self, mvk: &MasterVerifyingKey) -> Option<&Identity> {
+        self.body
+            .sender()
+            .filter(|c| c.is_issued_by(mvk))
+            .map(|c| c.identity())
+    }
+
+    /// Will write the entire stream to the argument writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&mut self, w: &mut W) -> Result<(), Error> {
+        loop {
+            match self.read().await {
+                Ok(buf) => w.write_all(buf).await?,
+                Err(Error::EndOfStream) => return w.flush().await.map_err(Error::from),
+                Err(e) => return Err(e),
+            };
+        }
+    }
+}
+
+/// Yields the plaintext, and fails with `ErrorKind::InvalidData` once it turns out not to be authentic.
+///
+/// **Warning**: for format version 0 streams, the plaintext can only be validated at the end of the stream.
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncOpenerUnsealed<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            let last = this.body.last();
+
+            if this.offset < last.len() {
+                let n = core::cmp::min(buf.len(), last.len() - this.offset);
+                buf[..n].copy_from_slice(&last[this.offset..this.offset + n]);
+                this.offset += n;
+                return Poll::Ready(Ok(n));
+            }
+
+            ready!(this.r.poll_fill(cx, this.readahead))?;
+
+            match this.step().map(|_| ()) {
+                Ok(()) => this.offset = 0,
+                Err(Error::EndOfStream) if this.body.is_valid() => return Poll::Ready(Ok(0)),
+                Err(Error::EndOfStream) => {
+                    return Poll::Ready(Err(Error::IntegrityViolation.into()))
+                }
+                Err(e) => return Poll::Ready(Err(e.into())),
+            }
+        }
+    }
+}
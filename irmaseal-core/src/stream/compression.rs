@@ -0,0 +1,164 @@
+use crate::*;
+
+#[cfg(feature = "compression")]
+use crate::stream::BLOCKSIZE;
+#[cfg(feature = "compression")]
+use miniz_oxide::{deflate::core::CompressorOxide, inflate::stream::InflateState};
+#[cfg(feature = "compression")]
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus, StreamResult};
+#[cfg(feature = "compression")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "compression")]
+const COMPRESSION_DEFLATE: u8 = 0x01;
+
+/// The compression level of DEFLATE, which ranges from 0 to 10.
+#[cfg(feature = "compression")]
+const DEFLATE_LEVEL: i32 = 6;
+
+/// Algorithm with which the content is compressed before it is sealed.
+///
+/// **Warning**: the length of compressed content depends on the content itself.
+/// Do not compress content that mixes secrets with data that can be influenced by an attacker.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Compression {
+    /// Do not compress the content.
+    #[default]
+    None,
+    /// Compress the content as raw DEFLATE.
+    #[cfg(feature = "compression")]
+    Deflate,
+}
+
+impl Compression {
+    /// The byte representation of the algorithm, as written to the header.
+    pub(crate) fn to_byte(self) -> Option<u8> {
+        match self {
+            Compression::None => None,
+            #[cfg(feature = "compression")]
+            Compression::Deflate => Some(COMPRESSION_DEFLATE),
+        }
+    }
+
+    /// Throws an IncorrectVersion error for algorithms that are not supported by this build.
+    pub(crate) fn from_byte(b: u8) -> Result<Compression, Error> {
+        match b {
+            #[cfg(feature = "compression")]
+            COMPRESSION_DEFLATE => Ok(Compression::Deflate),
+            _ => Err(Error::IncorrectVersion),
+        }
+    }
+}
+
+/// Streaming compressor of the content, of which the output is written into segments.
+#[cfg(feature = "compression")]
+pub(crate) struct Compressor(Box<CompressorOxide>);
+
+#[cfg(feature = "compression")]
+impl Compressor {
+    pub fn new(compression: Compression) -> Option<Compressor> {
+        match compression {
+            Compression::None => None,
+            Compression::Deflate => {
+                let flags = miniz_oxide::deflate::core::create_comp_flags_from_zip_params(
+                    DEFLATE_LEVEL,
+                    -15,
+                    0,
+                );
+                Some(Compressor(Box::new(CompressorOxide::new(flags))))
+            }
+        }
+    }
+
+    /// Compress from `input` into `output`, and finish the compressed stream if `finish` is set.
+    ///
+    /// Yields the number of bytes consumed and written, and whether the compressed stream is finished.
+    pub fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<(usize, usize, bool), Error> {
+        let flush = if finish {
+            MZFlush::Finish
+        } else {
+            MZFlush::None
+        };
+        let res = miniz_oxide::deflate::stream::deflate(&mut self.0, input, output, flush);
+
+        match res.status {
+            Ok(status) => Ok((
+                res.bytes_consumed,
+                res.bytes_written,
+                status == MZStatus::StreamEnd,
+            )),
+            Err(MZError::Buf) => Ok((res.bytes_consumed, res.bytes_written, false)),
+            Err(_) => Err(Error::ConstraintViolation),
+        }
+    }
+}
+
+/// Streaming decompressor of the content of the segments.
+#[cfg(feature = "compression")]
+pub(crate) struct Decompressor {
+    state: Box<InflateState>,
+    buf: Zeroizing<[u8; BLOCKSIZE]>,
+    len: usize,
+    finished: bool,
+}
+
+#[cfg(feature = "compression")]
+impl Decompressor {
+    pub fn new(compression: Compression) -> Option<Decompressor> {
+        match compression {
+            Compression::None => None,
+            Compression::Deflate => Some(Decompressor {
+                state: InflateState::new_boxed(DataFormat::Raw),
+                buf: Zeroizing::new([0u8; BLOCKSIZE]),
+                len: 0,
+                finished: false,
+            }),
+        }
+    }
+
+    /// Decompress up to `BLOCKSIZE` bytes from `input`, and yield the number of bytes consumed.
+    ///
+    /// The decompressed bytes are available through `last`, which may be empty if more input is required.
+    /// Throws a FormatViolation if the input is not valid compressed data, or follows the end of it.
+    pub fn decompress(&mut self, input: &[u8]) -> Result<usize, Error> {
+        if self.finished && !input.is_empty() {
+            return Err(Error::FormatViolation);
+        }
+
+        let StreamResult {
+            bytes_consumed,
+            bytes_written,
+            status,
+        } = miniz_oxide::inflate::stream::inflate(
+            &mut self.state,
+            input,
+            &mut self.buf[..],
+            MZFlush::None,
+        );
+
+        self.len = bytes_written;
+
+        match status {
+            Ok(MZStatus::StreamEnd) => self.finished = true,
+            Ok(_) | Err(MZError::Buf) => {}
+            Err(_) => return Err(Error::FormatViolation),
+        }
+
+        Ok(bytes_consumed)
+    }
+
+    /// The bytes that were decompressed by the last call to `decompress`.
+    pub fn last(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Whether the end of the compressed data has been reached.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
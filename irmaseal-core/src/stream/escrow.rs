@@ -0,0 +1,173 @@
+//! Escrow mode, in which the body key is additionally wrapped for the X25519 escrow key of
+//! an organisation, such that it can recover every stream its members seal.
+//!
+//! The escrow key pair is generated and held by the organisation itself, and is not tied to
+//! an identity. The escrow public key is listed in the header, such that anyone can tell which
+//! organisation is able to recover the stream.
+
+use crate::stream::*;
+use crate::*;
+
+use arrayref::array_ref;
+use rand::{CryptoRng, Rng};
+use zeroize::Zeroizing;
+
+use crate::stream::util::Key;
+
+pub(crate) const ESCROW_KEYSIZE: usize = 32;
+
+/// The public key of an organisation, for which the body key is wrapped in escrow mode.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EscrowPublicKey(x25519_dalek::PublicKey);
+
+/// The secret key of an organisation, with which it can unseal every stream in escrow mode.
+///
+/// Is overwritten with zeroes when dropped.
+pub struct EscrowSecretKey(x25519_dalek::StaticSecret);
+
+impl core::fmt::Debug for EscrowPublicKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_tuple("EscrowPublicKey")
+            .field(self.0.as_bytes())
+            .finish()
+    }
+}
+
+fn generate_secret<R: Rng + CryptoRng>(rng: &mut R) -> x25519_dalek::StaticSecret {
+    let mut bytes = Zeroizing::new([0u8; ESCROW_KEYSIZE]);
+    rng.fill_bytes(&mut bytes[..]);
+    x25519_dalek::StaticSecret::from(*bytes)
+}
+
+/// Generate an escrow key pair for an organisation.
+pub fn generate_escrow_keys<R: Rng + CryptoRng>(rng: &mut R) -> (EscrowPublicKey, EscrowSecretKey) {
+    let secret = generate_secret(rng);
+    let public = x25519_dalek::PublicKey::from(&secret);
+
+    (EscrowPublicKey(public), EscrowSecretKey(secret))
+}
+
+impl EscrowPublicKey {
+    pub fn to_bytes(&self) -> [u8; ESCROW_KEYSIZE] {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<EscrowPublicKey> {
+        if b.len() != ESCROW_KEYSIZE {
+            return None;
+        }
+
+        Some(EscrowPublicKey(x25519_dalek::PublicKey::from(*array_ref![
+            b,
+            0,
+            ESCROW_KEYSIZE
+        ])))
+    }
+}
+
+impl EscrowSecretKey {
+    /// The escrow public key, for which the body key has to be wrapped.
+    pub fn public_key(&self) -> EscrowPublicKey {
+        EscrowPublicKey(x25519_dalek::PublicKey::from(&self.0))
+    }
+
+    pub fn to_bytes(&self) -> Zeroizing<[u8; ESCROW_KEYSIZE]> {
+        Zeroizing::new(self.0.to_bytes())
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<EscrowSecretKey> {
+        if b.len() != ESCROW_KEYSIZE {
+            return None;
+        }
+
+        Some(EscrowSecretKey(x25519_dalek::StaticSecret::from(
+            *array_ref![b, 0, ESCROW_KEYSIZE],
+        )))
+    }
+}
+
+/// The body key as wrapped for the escrow key, as listed in the header.
+pub(crate) struct EscrowRecipient {
+    key: EscrowPublicKey,
+    ephemeral: x25519_dalek::PublicKey,
+    wrapped_key: [u8; KEYSIZE],
+}
+
+/// Derive the key with which the body key is wrapped from the X25519 shared secret,
+/// which is bound to both public keys.
+///
+/// Throws a FormatViolation when the ephemeral key is of low order.
+fn wrapping_key(
+    ss: x25519_dalek::SharedSecret,
+    key: &EscrowPublicKey,
+    ephemeral: &x25519_dalek::PublicKey,
+) -> Result<Zeroizing<[u8; 3 * ESCROW_KEYSIZE]>, Error> {
+    if !ss.was_contributory() {
+        return Err(Error::FormatViolation);
+    }
+
+    let mut res = Zeroizing::new([0u8; 3 * ESCROW_KEYSIZE]);
+    res[..ESCROW_KEYSIZE].copy_from_slice(ss.as_bytes());
+    res[ESCROW_KEYSIZE..2 * ESCROW_KEYSIZE].copy_from_slice(key.0.as_bytes());
+    res[2 * ESCROW_KEYSIZE..].copy_from_slice(ephemeral.as_bytes());
+
+    Ok(res)
+}
+
+impl EscrowRecipient {
+    /// Wrap the body key for the escrow key, using a fresh ephemeral key.
+    pub fn seal<R: Rng + CryptoRng>(
+        key: &EscrowPublicKey,
+        body_key: &[u8; KEYSIZE],
+        rng: &mut R,
+    ) -> Result<EscrowRecipient, Error> {
+        let secret = generate_secret(rng);
+        let ephemeral = x25519_dalek::PublicKey::from(&secret);
+        let wk = wrapping_key(secret.diffie_hellman(&key.0), key, &ephemeral)
+            .or(Err(Error::ConstraintViolation))?;
+
+        Ok(EscrowRecipient {
+            key: *key,
+            ephemeral,
+            wrapped_key: *crate::stream::util::mask_key(&wk[..], body_key),
+        })
+    }
+
+    pub fn key(&self) -> &EscrowPublicKey {
+        &self.key
+    }
+
+    /// Unwrap the body key.
+    ///
+    /// Throws a WrongKey error when the secret key does not belong to the escrow public key.
+    pub fn unwrap(&self, sk: &EscrowSecretKey) -> Result<Key, Error> {
+        if sk.public_key() != self.key {
+            return Err(Error::WrongKey);
+        }
+
+        let wk = wrapping_key(
+            sk.0.diffie_hellman(&self.ephemeral),
+            &self.key,
+            &self.ephemeral,
+        )?;
+        Ok(crate::stream::util::mask_key(&wk[..], &self.wrapped_key))
+    }
+
+    pub fn read_from<R: Readable>(mut r: R) -> Result<EscrowRecipient, Error> {
+        let key = *array_ref![r.read_bytes_strict(ESCROW_KEYSIZE)?, 0, ESCROW_KEYSIZE];
+        let ephemeral = *array_ref![r.read_bytes_strict(ESCROW_KEYSIZE)?, 0, ESCROW_KEYSIZE];
+        let wrapped_key = *array_ref![r.read_bytes_strict(KEYSIZE)?, 0, KEYSIZE];
+
+        Ok(EscrowRecipient {
+            key: EscrowPublicKey(x25519_dalek::PublicKey::from(key)),
+            ephemeral: x25519_dalek::PublicKey::from(ephemeral),
+            wrapped_key,
+        })
+    }
+
+    pub fn write_to<W: Writable>(&self, mut w: W) -> Result<(), Error> {
+        w.write(self.key.0.as_bytes())?;
+        w.write(self.ephemeral.as_bytes())?;
+        w.write(&self.wrapped_key)
+    }
+}
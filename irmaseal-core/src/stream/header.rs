@@ -0,0 +1,949 @@
+use crate::kem::{Artifact, Kem, KiltzVahlisOne, Scheme};
+use crate::metadata::{MetadataBuf, MAX_METADATASIZE};
+use crate::stream::segment::{open_single, seal_single, Segments};
+use crate::stream::util::{
+    ArchiveReader, CountingWriter, DigestReader, DigestWriter, Key, KeySchedule, LimitedReader,
+    Sink,
+};
+use crate::stream::*;
+use crate::*;
+
+#[cfg(feature = "escrow")]
+use crate::stream::escrow::{EscrowPublicKey, EscrowRecipient, EscrowSecretKey};
+#[cfg(feature = "hybrid")]
+pub(crate) use crate::stream::hybrid::HybridSecretKey;
+#[cfg(feature = "hybrid")]
+use crate::stream::hybrid::HYBRID_CIPHERTEXT_SIZE;
+#[cfg(feature = "passphrase")]
+use crate::stream::passphrase::PassphraseRecipient;
+#[cfg(feature = "signing")]
+use crate::stream::sender::SenderCertificate;
+#[cfg(feature = "signing")]
+pub(crate) use crate::stream::sender::SignedBody;
+
+use arrayref::array_ref;
+use arrayvec::ArrayVec;
+use digest::Digest;
+use rand::{CryptoRng, Rng};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// A recipient of an IRMAseal encrypted bytestream, as listed in its header.
+pub struct Recipient {
+    identity: Identity,
+    /// Is only set for recipients of which the attribute value is hidden.
+    hint: Option<[u8; HINTSIZE]>,
+    ciphertext: Ciphertext,
+    wrapped_key: Option<[u8; KEYSIZE]>,
+    /// The Kyber ciphertext, which is only set for recipients in hybrid mode.
+    #[cfg(feature = "hybrid")]
+    hybrid: Option<[u8; HYBRID_CIPHERTEXT_SIZE]>,
+}
+
+/// A way in which a stream can be unsealed, as listed in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockMethod {
+    /// The user secret key of one of the recipients, using `unseal`.
+    Identity,
+    /// The user secret key and the post-quantum secret key of one of the recipients, using `unseal_hybrid`.
+    #[cfg(feature = "hybrid")]
+    HybridIdentity,
+    /// A passphrase, using `unseal_with_passphrase`.
+    #[cfg(feature = "passphrase")]
+    Passphrase,
+    /// The escrow secret key of the organisation, using `unseal_with_escrow`.
+    #[cfg(feature = "escrow")]
+    Escrow,
+}
+
+/// Without hybrid mode, no post-quantum secret key can be provided.
+#[cfg(not(feature = "hybrid"))]
+pub(crate) enum HybridSecretKey {}
+
+/// Without signed mode, no stream is signed by its sender.
+#[cfg(not(feature = "signing"))]
+pub(crate) enum SignedBody {}
+
+/// The KEM ciphertext of a single recipient, of which the size depends on the scheme.
+type Ciphertext = ArrayVec<[u8; MAX_CIPHERTEXT_SIZE]>;
+
+impl Recipient {
+    fn new(
+        identity: &Identity,
+        hidden: bool,
+        ciphertext: Ciphertext,
+        wrapped_key: [u8; KEYSIZE],
+    ) -> Recipient {
+        let (identity, hint) = if hidden {
+            let hint = crate::stream::util::recipient_hint(identity, &ciphertext);
+            let mut identity = identity.clone();
+            identity.attribute.value = None;
+
+            (identity, Some(hint))
+        } else {
+            (identity.clone(), None)
+        };
+
+        Recipient {
+            identity,
+            hint,
+            ciphertext,
+            wrapped_key: Some(wrapped_key),
+            #[cfg(feature = "hybrid")]
+            hybrid: None,
+        }
+    }
+
+    /// The identity for which the UserSecretKey needs to be retrieved.
+    ///
+    /// If the recipient is hidden, the attribute value is not set.
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Whether the attribute value of this recipient is hidden.
+    pub fn is_hidden(&self) -> bool {
+        self.hint.is_some()
+    }
+
+    /// Whether the body key is also encapsulated for the post-quantum key of this recipient.
+    ///
+    /// Such a recipient can only unseal the stream using its `HybridSecretKey`.
+    pub fn is_hybrid(&self) -> bool {
+        self.hybrid_ciphertext().is_some()
+    }
+
+    fn hybrid_ciphertext(&self) -> Option<&[u8]> {
+        #[cfg(feature = "hybrid")]
+        return self.hybrid.as_ref().map(|c| &c[..]);
+        #[cfg(not(feature = "hybrid"))]
+        return None;
+    }
+
+    /// Whether this recipient corresponds to the identity.
+    ///
+    /// A hidden recipient can only be recognised by means of a short hint of the attribute value.
+    /// Hence a hidden recipient may match identities with other values on rare occasions.
+    pub fn matches(&self, identity: &Identity) -> bool {
+        match self.hint {
+            None => &self.identity == identity,
+            Some(hint) => {
+                self.identity.timestamp == identity.timestamp
+                    && self.identity.attribute.atype == identity.attribute.atype
+                    && identity.attribute.value.is_some()
+                    && crate::stream::util::recipient_hint(identity, &self.ciphertext) == hint
+            }
+        }
+    }
+
+    /// Read the value of a format version 2 recipient field.
+    ///
+    /// The KEM ciphertext spans the value up to the wrapped key, such that it can be read regardless of the scheme.
+    fn read_from<R: Readable>(r: &mut LimitedReader<R>, hidden: bool) -> Result<Recipient, Error> {
+        let identity = Identity::read_from(r)?;
+
+        let hint = if hidden {
+            if identity.attribute.value.is_some() {
+                return Err(Error::FormatViolation);
+            }
+
+            Some(*array_ref![r.read_bytes_strict(HINTSIZE)?, 0, HINTSIZE])
+        } else {
+            None
+        };
+
+        let len = r
+            .remaining()
+            .checked_sub(KEYSIZE)
+            .ok_or(Error::FormatViolation)?;
+        let ciphertext = read_ciphertext(r, len)?;
+        let wrapped_key = *array_ref![r.read_bytes_strict(KEYSIZE)?, 0, KEYSIZE];
+
+        Ok(Recipient {
+            identity,
+            hint,
+            ciphertext,
+            wrapped_key: Some(wrapped_key),
+            #[cfg(feature = "hybrid")]
+            hybrid: None,
+        })
+    }
+
+    /// Write the value of a format version 2 recipient field.
+    fn write_to<W: Writable>(&self, w: &mut W) -> Result<(), Error> {
+        let wrapped_key = self.wrapped_key.ok_or(Error::ConstraintViolation)?;
+
+        self.identity.write_to(w)?;
+        if let Some(hint) = &self.hint {
+            w.write(hint)?;
+        }
+        w.write(&self.ciphertext)?;
+        w.write(&wrapped_key)
+    }
+}
+
+/// The part of the header that is required to authenticate the stream.
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Preamble {
+    /// The header bytes of a version 0 stream are fed to the HMAC verbatim.
+    V0(ArrayVec<[u8; 2048]>),
+    /// The header of a version 2 stream is authenticated with every segment as its digest.
+    V2([u8; DIGESTSIZE], [u8; NONCE_PREFIX_SIZE]),
+}
+
+/// The keys with which the body of a stream can be opened, after unsealing the header.
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum BodyKeys<'a> {
+    /// The body keys of a version 0 stream are derived from the KEM shared secret itself.
+    V0((Key, Key), &'a [u8]),
+    /// The segments of a version 2 stream can be opened with the derived AEAD key.
+    /// The signature of a signed stream is verified along with them.
+    V2(Segments, BodyFormat, Option<SignedBody>),
+}
+
+/// How the content is laid out in the segments of a version 2 stream.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyFormat {
+    /// The size of the plaintext of every segment, including any padding.
+    pub block_size: usize,
+    /// Whether every segment is padded to its full size.
+    pub padded: bool,
+    /// The algorithm with which the content is compressed before it is sealed.
+    #[cfg_attr(not(feature = "compression"), allow(dead_code))]
+    pub compression: Compression,
+}
+
+impl Default for BodyFormat {
+    fn default() -> BodyFormat {
+        BodyFormat {
+            block_size: BLOCKSIZE,
+            padded: false,
+            compression: Compression::default(),
+        }
+    }
+}
+
+impl BodyFormat {
+    /// The size of every segment, including its authentication tag, except for the final one.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn segment_size(&self) -> usize {
+        self.block_size + TAGSIZE
+    }
+}
+
+/// Fields of which the type has this bit set must be understood by the reader.
+/// Other fields that are not understood are skipped, but are still authenticated.
+pub(crate) const FIELD_CRITICAL: u8 = 0x80;
+/// The identity, IBE ciphertext and wrapped body key of a single recipient. Occurs at least once.
+pub(crate) const FIELD_RECIPIENT: u8 = 0x01 | FIELD_CRITICAL;
+/// The random prefix of the nonces with which the segments are sealed. Occurs exactly once.
+pub(crate) const FIELD_NONCE_PREFIX: u8 = 0x02 | FIELD_CRITICAL;
+/// The size of the plaintext of every segment as a big endian `u32`. Occurs at most once,
+/// and defaults to `BLOCKSIZE`.
+pub(crate) const FIELD_BLOCK_SIZE: u8 = 0x03 | FIELD_CRITICAL;
+/// The sealed metadata of the content. Occurs at most once.
+pub(crate) const FIELD_METADATA: u8 = 0x04;
+/// A recipient of which only the attribute type and a hint of the attribute value are exposed.
+pub(crate) const FIELD_HIDDEN_RECIPIENT: u8 = 0x05 | FIELD_CRITICAL;
+/// Marks that every segment is padded to its full size. Has no value, and occurs at most once.
+pub(crate) const FIELD_PADDING: u8 = 0x06 | FIELD_CRITICAL;
+/// The algorithm with which the content is compressed, as a single byte. Occurs at most once.
+pub(crate) const FIELD_COMPRESSION: u8 = 0x07 | FIELD_CRITICAL;
+/// The KEM with which the body key is sealed for the recipients, as a single byte.
+/// Occurs at most once, and defaults to Kiltz-Vahlis IBE1.
+pub(crate) const FIELD_SCHEME: u8 = 0x08 | FIELD_CRITICAL;
+/// The policy with which the timestamps of the recipients are rounded, as a single byte.
+/// Occurs at most once, and defaults to exact timestamps. Is not critical, as the recipients
+/// already carry the rounded timestamps.
+pub(crate) const FIELD_TIMESTAMP_POLICY: u8 = 0x0E;
+/// The commitment to the body key, as derived by the `KeySchedule`.
+/// Occurs exactly once.
+pub(crate) const FIELD_KEY_COMMITMENT: u8 = 0x0A | FIELD_CRITICAL;
+/// The Kyber ciphertext of the recipient that precedes it, in hybrid mode.
+/// Either follows every recipient exactly once, or does not occur at all.
+#[cfg(feature = "hybrid")]
+pub(crate) const FIELD_HYBRID: u8 = 0x09 | FIELD_CRITICAL;
+/// The certificate of the sender, which implies that the body is trailed by its signature.
+/// Occurs at most once.
+#[cfg(feature = "signing")]
+pub(crate) const FIELD_SENDER: u8 = 0x0B | FIELD_CRITICAL;
+/// The body key wrapped for a passphrase, along with the salt and the cost of its derivation.
+/// Occurs at most once.
+#[cfg(feature = "passphrase")]
+pub(crate) const FIELD_PASSPHRASE: u8 = 0x0C | FIELD_CRITICAL;
+/// The body key wrapped for the escrow key, along with the escrow key and the ephemeral key.
+/// Occurs at most once.
+#[cfg(feature = "escrow")]
+pub(crate) const FIELD_ESCROW: u8 = 0x0D | FIELD_CRITICAL;
+
+/// The maximum size of all fields of a format version 2 header.
+pub(crate) const MAX_HEADERSIZE: usize = 65536;
+
+/// Write a single typed field, of which the value is written by `f`.
+///
+/// The value is written twice, as its length has to be determined first.
+fn write_field<F>(w: &mut dyn Writable, ftype: u8, f: F) -> Result<(), Error>
+where
+    F: Fn(&mut dyn Writable) -> Result<(), Error>,
+{
+    let len = written_len(&f)?;
+    if len > usize::from(u16::MAX) {
+        return Err(Error::ConstraintViolation);
+    }
+
+    w.write(&[ftype])?;
+    w.write(&(len as u16).to_be_bytes())?;
+    f(w)
+}
+
+/// The number of bytes that are written by `f`.
+fn written_len<F>(f: F) -> Result<usize, Error>
+where
+    F: Fn(&mut dyn Writable) -> Result<(), Error>,
+{
+    let mut counter = CountingWriter::new(Sink);
+    f(&mut counter)?;
+    Ok(counter.disclose().1 as usize)
+}
+
+/// The IRMAseal header, as read from the start of a bytestream.
+///
+/// A format version 2 header consists of the prelude, the version, the length of the fields as a
+/// big endian `u32`, and the fields. Each field consists of its type, the length of its value
+/// as a big endian `u16`, and its value.
+pub(crate) struct Header {
+    pub recipients: ArrayVec<[Recipient; MAX_RECIPIENTS]>,
+    pub preamble: Preamble,
+    /// The KEM with which the body key is sealed for the recipients.
+    pub scheme: Scheme,
+    /// The metadata, sealed with a key that is derived from the body key.
+    pub metadata: Option<SealedMetadata>,
+    pub format: BodyFormat,
+    /// The commitment to the body key, which is only set for format version 2 streams.
+    pub commitment: Option<[u8; KEYSIZE]>,
+    /// The policy with which the timestamps of the recipients are rounded.
+    pub timestamp_policy: TimestampPolicy,
+    /// The certificate of the sender, which is only set for signed streams.
+    #[cfg(feature = "signing")]
+    pub sender: Option<SenderCertificate>,
+    /// The body key wrapped for a passphrase, which is only set for streams in passphrase mode.
+    #[cfg(feature = "passphrase")]
+    pub passphrase: Option<PassphraseRecipient>,
+    /// The body key wrapped for the escrow key, which is only set for streams in escrow mode.
+    #[cfg(feature = "escrow")]
+    pub escrow: Option<EscrowRecipient>,
+}
+
+/// Must be at least MAX_METADATASIZE + TAGSIZE.
+pub(crate) type SealedMetadata = ArrayVec<[u8; 2048]>;
+
+/// Seal the metadata, such that it can be opened before the body.
+fn seal_metadata(key: &[u8; KEYSIZE], metadata: &Metadata) -> Result<SealedMetadata, Error> {
+    let mut buf = MetadataBuf::new();
+    metadata
+        .write_to(&mut buf)
+        .or(Err(Error::ConstraintViolation))?;
+
+    let tag = seal_single(key, &mut buf)?;
+
+    let mut res = ArrayVec::new();
+    res.try_extend_from_slice(&buf)
+        .and_then(|_| res.try_extend_from_slice(&tag))
+        .or(Err(Error::ConstraintViolation))?;
+    Ok(res)
+}
+
+/// Open the metadata, which throws an IntegrityViolation if it is not authentic.
+fn open_metadata(key: &[u8; KEYSIZE], sealed: &[u8]) -> Result<Metadata, Error> {
+    if sealed.len() < TAGSIZE || sealed.len() > MAX_METADATASIZE + TAGSIZE {
+        return Err(Error::FormatViolation);
+    }
+
+    let mut buf = [0u8; MAX_METADATASIZE];
+    let len = sealed.len() - TAGSIZE;
+    let (content, tag) = sealed.split_at(len);
+
+    buf[..len].copy_from_slice(content);
+    open_single(key, &mut buf[..len], tag)?;
+
+    Metadata::read_from(&mut crate::util::SliceReader::new(&buf[..len]))
+}
+
+fn read_ciphertext<R: Readable>(r: &mut R, len: usize) -> Result<Ciphertext, Error> {
+    if len > MAX_CIPHERTEXT_SIZE {
+        return Err(Error::FormatViolation);
+    }
+
+    let mut res = Ciphertext::new();
+    res.try_extend_from_slice(r.read_bytes_strict(len)?)
+        .or(Err(Error::FormatViolation))?;
+    Ok(res)
+}
+
+impl Header {
+    /// Read the header, and yield the stream continuation.
+    pub fn read_from<R: Readable>(r: R) -> Result<(Header, R), Error> {
+        let mut ar = ArchiveReader::<R, [u8; 2048]>::new(r);
+
+        let prelude = ar.read_bytes_strict(PRELUDE.len())?;
+        if prelude != PRELUDE {
+            return Err(Error::NotIRMASEAL);
+        }
+
+        let mut recipients = ArrayVec::new();
+
+        match ar.read_byte()? {
+            FORMAT_VERSION_V0 => {
+                let identity = Identity::read_from(&mut ar)?;
+                let ciphertext =
+                    read_ciphertext(&mut ar, <KiltzVahlisOne as Kem>::Ciphertext::SIZE)?;
+
+                recipients.push(Recipient {
+                    identity,
+                    hint: None,
+                    ciphertext,
+                    wrapped_key: None,
+                    #[cfg(feature = "hybrid")]
+                    hybrid: None,
+                });
+
+                let (headerbuf, r) = ar.disclose();
+
+                Ok((
+                    Header {
+                        recipients,
+                        preamble: Preamble::V0(headerbuf),
+                        scheme: Scheme::KiltzVahlisOne,
+                        metadata: None,
+                        format: BodyFormat::default(),
+                        commitment: None,
+                        timestamp_policy: TimestampPolicy::Exact,
+                        #[cfg(feature = "signing")]
+                        sender: None,
+                        #[cfg(feature = "passphrase")]
+                        passphrase: None,
+                        #[cfg(feature = "escrow")]
+                        escrow: None,
+                    },
+                    r,
+                ))
+            }
+            FORMAT_VERSION_V2 => {
+                let (headerbuf, r) = ar.disclose();
+
+                let mut digest = HeaderDigest::new();
+                digest.input(&headerbuf);
+                let mut dr = DigestReader::new(digest, r);
+
+                let len = u32::from_be_bytes(*array_ref![dr.read_bytes_strict(4)?, 0, 4]) as usize;
+                if len > MAX_HEADERSIZE {
+                    return Err(Error::FormatViolation);
+                }
+
+                let mut fr = LimitedReader::new(dr, len);
+                let mut nonce_prefix = None;
+                let mut metadata = None;
+                let mut padded = false;
+                let mut compression = None;
+                let mut block_size = None;
+                let mut scheme = None;
+                let mut commitment = None;
+                let mut timestamp_policy = None;
+                #[cfg(feature = "signing")]
+                let mut sender = None;
+                #[cfg(feature = "passphrase")]
+                let mut passphrase = None;
+                #[cfg(feature = "escrow")]
+                let mut escrow = None;
+
+                while fr.remaining() > 0 {
+                    let ftype = fr.read_byte()?;
+                    let flen = u16::from_be_bytes(*array_ref![fr.read_bytes_strict(2)?, 0, 2]);
+                    let mut vr = LimitedReader::new(&mut fr, usize::from(flen));
+
+                    match ftype {
+                        FIELD_RECIPIENT | FIELD_HIDDEN_RECIPIENT => recipients
+                            .try_push(Recipient::read_from(
+                                &mut vr,
+                                ftype == FIELD_HIDDEN_RECIPIENT,
+                            )?)
+                            .or(Err(Error::FormatViolation))?,
+                        FIELD_NONCE_PREFIX if nonce_prefix.is_none() => {
+                            nonce_prefix = Some(*array_ref![
+                                vr.read_bytes_strict(NONCE_PREFIX_SIZE)?,
+                                0,
+                                NONCE_PREFIX_SIZE
+                            ]);
+                        }
+                        FIELD_METADATA if metadata.is_none() => {
+                            if vr.remaining() > MAX_METADATASIZE + TAGSIZE {
+                                return Err(Error::FormatViolation);
+                            }
+
+                            let mut sealed = ArrayVec::new();
+                            sealed
+                                .try_extend_from_slice(vr.read_bytes_strict(vr.remaining())?)
+                                .or(Err(Error::FormatViolation))?;
+                            metadata = Some(sealed);
+                        }
+                        FIELD_PADDING if !padded => padded = true,
+                        FIELD_COMPRESSION if compression.is_none() => {
+                            compression = Some(Compression::from_byte(vr.read_byte()?)?);
+                        }
+                        #[cfg(feature = "hybrid")]
+                        FIELD_HYBRID => {
+                            let recipient = recipients
+                                .last_mut()
+                                .filter(|r: &&mut Recipient| r.hybrid.is_none())
+                                .ok_or(Error::FormatViolation)?;
+                            recipient.hybrid = Some(*array_ref![
+                                vr.read_bytes_strict(HYBRID_CIPHERTEXT_SIZE)?,
+                                0,
+                                HYBRID_CIPHERTEXT_SIZE
+                            ]);
+                        }
+                        FIELD_BLOCK_SIZE if block_size.is_none() => {
+                            let size =
+                                u32::from_be_bytes(*array_ref![vr.read_bytes_strict(4)?, 0, 4])
+                                    as usize;
+                            if !valid_block_size(size) {
+                                return Err(Error::FormatViolation);
+                            }
+                            block_size = Some(size);
+                        }
+                        FIELD_SCHEME if scheme.is_none() => {
+                            scheme = Some(Scheme::from_byte(vr.read_byte()?)?);
+                        }
+                        FIELD_KEY_COMMITMENT if commitment.is_none() => {
+                            commitment =
+                                Some(*array_ref![vr.read_bytes_strict(KEYSIZE)?, 0, KEYSIZE]);
+                        }
+                        FIELD_TIMESTAMP_POLICY if timestamp_policy.is_none() => {
+                            timestamp_policy = Some(TimestampPolicy::from_byte(vr.read_byte()?)?);
+                        }
+                        #[cfg(feature = "signing")]
+                        FIELD_SENDER if sender.is_none() => {
+                            sender = Some(SenderCertificate::read_from(&mut vr)?);
+                        }
+                        #[cfg(feature = "signing")]
+                        FIELD_SENDER => return Err(Error::FormatViolation),
+                        #[cfg(feature = "passphrase")]
+                        FIELD_PASSPHRASE if passphrase.is_none() => {
+                            passphrase = Some(PassphraseRecipient::read_from(&mut vr)?);
+                        }
+                        #[cfg(feature = "passphrase")]
+                        FIELD_PASSPHRASE => return Err(Error::FormatViolation),
+                        #[cfg(feature = "escrow")]
+                        FIELD_ESCROW if escrow.is_none() => {
+                            escrow = Some(EscrowRecipient::read_from(&mut vr)?);
+                        }
+                        #[cfg(feature = "escrow")]
+                        FIELD_ESCROW => return Err(Error::FormatViolation),
+                        FIELD_NONCE_PREFIX
+                        | FIELD_METADATA
+                        | FIELD_PADDING
+                        | FIELD_COMPRESSION
+                        | FIELD_BLOCK_SIZE
+                        | FIELD_SCHEME
+                        | FIELD_KEY_COMMITMENT
+                        | FIELD_TIMESTAMP_POLICY => return Err(Error::FormatViolation),
+                        t if t & FIELD_CRITICAL != 0 => return Err(Error::IncorrectVersion),
+                        _ => vr.skip()?,
+                    }
+
+                    if vr.remaining() != 0 {
+                        return Err(Error::FormatViolation);
+                    }
+                }
+
+                let nonce_prefix = nonce_prefix.ok_or(Error::FormatViolation)?;
+                if recipients.is_empty() || commitment.is_none() {
+                    return Err(Error::FormatViolation);
+                }
+
+                let hybrid = recipients.iter().filter(|r| r.is_hybrid()).count();
+                if hybrid != 0 && hybrid != recipients.len() {
+                    return Err(Error::FormatViolation);
+                }
+
+                // The timestamps of the recipients have to be rounded as the policy claims.
+                let timestamp_policy = timestamp_policy.unwrap_or_default();
+                if recipients.iter().any(|r| {
+                    let timestamp = r.identity().timestamp;
+                    timestamp_policy.round_down(timestamp) != Some(timestamp)
+                }) {
+                    return Err(Error::FormatViolation);
+                }
+
+                let (digest, r) = fr.disclose().disclose();
+                let mut headerdigest = [0u8; DIGESTSIZE];
+                headerdigest.copy_from_slice(&digest.result());
+
+                Ok((
+                    Header {
+                        recipients,
+                        preamble: Preamble::V2(headerdigest, nonce_prefix),
+                        scheme: scheme.unwrap_or_default(),
+                        metadata,
+                        format: BodyFormat {
+                            block_size: block_size.unwrap_or(BLOCKSIZE),
+                            padded,
+                            compression: compression.unwrap_or_default(),
+                        },
+                        commitment,
+                        timestamp_policy,
+                        #[cfg(feature = "signing")]
+                        sender,
+                        #[cfg(feature = "passphrase")]
+                        passphrase,
+                        #[cfg(feature = "escrow")]
+                        escrow,
+                    },
+                    r,
+                ))
+            }
+            _ => Err(Error::IncorrectVersion),
+        }
+    }
+
+    /// Write a header for the recipients, and yield the segments with which the body should be sealed.
+    ///
+    /// A single body key is generated, which is wrapped for every recipient separately.
+    /// Throws a ConstraintViolation when there are no recipients, or more than `MAX_RECIPIENTS`,
+    /// or when the block size is not valid.
+    pub fn write_to<K: Kem, R: Rng + CryptoRng, W: Writable>(
+        recipients: &[Identity],
+        options: &SealOptions,
+        block_size: usize,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: &mut W,
+    ) -> Result<Segments, Error> {
+        if recipients.is_empty()
+            || recipients.len() > MAX_RECIPIENTS
+            || !valid_block_size(block_size)
+        {
+            return Err(Error::ConstraintViolation);
+        }
+
+        #[cfg(feature = "hybrid")]
+        if matches!(&options.hybrid, Some(keys) if keys.len() != recipients.len()) {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let key = crate::stream::util::generate_key(rng);
+        let schedule = KeySchedule::new(&key);
+        let commitment = schedule.commitment();
+        let nonce_prefix = crate::stream::util::generate_nonce_prefix(rng);
+
+        let mut sealed = ArrayVec::<[Recipient; MAX_RECIPIENTS]>::new();
+        #[cfg_attr(not(feature = "hybrid"), allow(unused_variables))]
+        for (n, i) in recipients.iter().enumerate() {
+            let i = &Identity {
+                timestamp: options
+                    .timestamp_policy
+                    .round_down(i.timestamp)
+                    .ok_or(Error::ConstraintViolation)?,
+                ..i.clone()
+            };
+            let (c, ss) = K::encaps(&pk.0, i, rng);
+            let ss = Zeroizing::new(ss);
+
+            let mut ciphertext = Ciphertext::new();
+            ciphertext
+                .try_extend_from_slice(c.to_bytes().as_ref())
+                .or(Err(Error::ConstraintViolation))?;
+
+            #[allow(unused_mut)]
+            let mut recipient = Recipient::new(
+                i,
+                options.hide_values,
+                ciphertext,
+                *crate::stream::util::mask_key(ss.as_ref(), &key),
+            );
+
+            // In hybrid mode, the body key is wrapped using both shared secrets instead.
+            #[cfg(feature = "hybrid")]
+            if let Some(keys) = &options.hybrid {
+                let (pq_c, pq_ss) = keys[n].encaps(rng)?;
+                let combined = crate::stream::hybrid::combine(ss.as_ref(), &pq_ss[..], &pq_c);
+
+                recipient.wrapped_key = Some(*crate::stream::util::mask_key(&combined[..], &key));
+                recipient.hybrid = Some(pq_c);
+            }
+
+            sealed.push(recipient);
+        }
+
+        #[cfg(feature = "passphrase")]
+        let passphrase = match &options.passphrase {
+            Some(passphrase) => Some(PassphraseRecipient::seal(passphrase, &key, rng)?),
+            None => None,
+        };
+
+        #[cfg(feature = "escrow")]
+        let escrow = match &options.escrow {
+            Some(escrow) => Some(EscrowRecipient::seal(escrow, &key, rng)?),
+            None => None,
+        };
+
+        let metadata = match &options.metadata {
+            Some(metadata) => Some(seal_metadata(&schedule.metadata_key(), metadata)?),
+            None => None,
+        };
+
+        let fields = |w: &mut dyn Writable| -> Result<(), Error> {
+            write_field(w, FIELD_SCHEME, |w| w.write(&[K::SCHEME.to_byte()]))?;
+
+            for r in sealed.iter() {
+                let ftype = if r.is_hidden() {
+                    FIELD_HIDDEN_RECIPIENT
+                } else {
+                    FIELD_RECIPIENT
+                };
+
+                write_field(w, ftype, |mut w| r.write_to(&mut w))?;
+
+                #[cfg(feature = "hybrid")]
+                if let Some(c) = &r.hybrid {
+                    write_field(w, FIELD_HYBRID, |w| w.write(c))?;
+                }
+            }
+
+            #[cfg(feature = "passphrase")]
+            if let Some(passphrase) = &passphrase {
+                write_field(w, FIELD_PASSPHRASE, |w| passphrase.write_to(w))?;
+            }
+
+            #[cfg(feature = "escrow")]
+            if let Some(escrow) = &escrow {
+                write_field(w, FIELD_ESCROW, |w| escrow.write_to(w))?;
+            }
+
+            write_field(w, FIELD_NONCE_PREFIX, |w| w.write(&nonce_prefix))?;
+            write_field(w, FIELD_KEY_COMMITMENT, |w| w.write(&commitment))?;
+
+            if let Some(metadata) = &metadata {
+                write_field(w, FIELD_METADATA, |w| w.write(metadata))?;
+            }
+
+            if options.padding != Padding::None {
+                write_field(w, FIELD_PADDING, |_| Ok(()))?;
+            }
+
+            if let Some(compression) = options.compression.to_byte() {
+                write_field(w, FIELD_COMPRESSION, |w| w.write(&[compression]))?;
+            }
+
+            if let Some(policy) = options.timestamp_policy.to_byte() {
+                write_field(w, FIELD_TIMESTAMP_POLICY, |w| w.write(&[policy]))?;
+            }
+
+            if block_size != BLOCKSIZE {
+                write_field(w, FIELD_BLOCK_SIZE, |w| {
+                    w.write(&(block_size as u32).to_be_bytes())
+                })?;
+            }
+
+            #[cfg(feature = "signing")]
+            if let Some(sender) = &options.sender {
+                write_field(w, FIELD_SENDER, |mut w| {
+                    sender.certificate().write_to(&mut w)
+                })?;
+            }
+
+            Ok(())
+        };
+
+        let len = written_len(fields)?;
+        if len > MAX_HEADERSIZE {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let mut dw = DigestWriter::new(HeaderDigest::new(), w);
+        dw.write(&PRELUDE)?;
+        dw.write(&[FORMAT_VERSION])?;
+        dw.write(&(len as u32).to_be_bytes())?;
+        fields(&mut dw)?;
+
+        let (digest, _) = dw.disclose();
+        let mut headerdigest = [0u8; DIGESTSIZE];
+        headerdigest.copy_from_slice(&digest.result());
+
+        Ok(Segments::new(
+            &schedule.segment_key(&headerdigest),
+            &nonce_prefix,
+            &headerdigest,
+        ))
+    }
+
+    /// Unseal the body key and the metadata for one of the recipients.
+    ///
+    /// Throws an UnknownRecipient error when none of the `recipients` matches the identity,
+    /// and an IncorrectScheme error when the header was sealed using another KEM,
+    /// or when `hybrid` is not provided for exactly the recipients in hybrid mode.
+    ///
+    /// Throws a WrongKey error when the unsealed body key does not match the commitment,
+    /// which can only be detected for format version 2 headers.
+    ///
+    /// The hint of a hidden recipient may also match other identities, hence every recipient
+    /// that matches is tried until the body key of one of them matches the commitment.
+    pub fn unseal<K: Kem>(
+        &self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+    ) -> Result<(BodyKeys<'_>, Option<Metadata>), Error> {
+        if self.scheme != K::SCHEME {
+            return Err(Error::IncorrectScheme);
+        }
+
+        let mut res = Err(Error::UnknownRecipient);
+        for recipient in self.recipients.iter().filter(|r| r.matches(identity)) {
+            res = self.unseal_recipient(recipient, usk, hybrid);
+            if !matches!(res, Err(Error::WrongKey)) {
+                break;
+            }
+        }
+
+        res
+    }
+
+    /// Unseal the body key and the metadata for the recipient, using the key of its identity.
+    fn unseal_recipient<K: Kem>(
+        &self,
+        recipient: &Recipient,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+    ) -> Result<(BodyKeys<'_>, Option<Metadata>), Error> {
+        let c = K::Ciphertext::from_bytes(&recipient.ciphertext).ok_or(Error::FormatViolation)?;
+        let ss = Zeroizing::new(K::decaps(&usk.0, &c));
+
+        #[cfg(feature = "hybrid")]
+        let combined;
+        let ss = match (recipient.hybrid_ciphertext(), hybrid) {
+            (None, None) => ss.as_ref(),
+            #[cfg(feature = "hybrid")]
+            (Some(pq_c), Some(hsk)) => {
+                combined =
+                    crate::stream::hybrid::combine(ss.as_ref(), &hsk.decaps(pq_c)?[..], pq_c);
+                &combined[..]
+            }
+            _ => return Err(Error::IncorrectScheme),
+        };
+
+        match (&self.preamble, recipient.wrapped_key) {
+            (Preamble::V0(headerbuf), None) => Ok((
+                BodyKeys::V0(crate::stream::util::derive_keys(ss), headerbuf),
+                None,
+            )),
+            (Preamble::V2(..), Some(wrapped_key)) => {
+                self.open(&crate::stream::util::mask_key(ss, &wrapped_key))
+            }
+            _ => Err(Error::FormatViolation),
+        }
+    }
+
+    /// Unseal the body key and the metadata for the passphrase.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed in passphrase mode,
+    /// and a WrongKey error when the passphrase is not correct.
+    #[cfg(feature = "passphrase")]
+    pub fn unseal_passphrase(
+        &self,
+        passphrase: &str,
+    ) -> Result<(BodyKeys<'_>, Option<Metadata>), Error> {
+        let recipient = self.passphrase.as_ref().ok_or(Error::UnknownRecipient)?;
+        self.open(&recipient.unwrap(passphrase)?)
+    }
+
+    /// Unseal the body key and the metadata for the escrow key.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed in escrow mode,
+    /// and a WrongKey error when it was sealed for another escrow key.
+    #[cfg(feature = "escrow")]
+    pub fn unseal_escrow(
+        &self,
+        sk: &EscrowSecretKey,
+    ) -> Result<(BodyKeys<'_>, Option<Metadata>), Error> {
+        let recipient = self.escrow.as_ref().ok_or(Error::UnknownRecipient)?;
+        self.open(&recipient.unwrap(sk)?)
+    }
+
+    /// The escrow key for which the body key is wrapped, if any.
+    #[cfg(feature = "escrow")]
+    pub fn escrow(&self) -> Option<&EscrowPublicKey> {
+        self.escrow.as_ref().map(|r| r.key())
+    }
+
+    /// Derive the keys with which the body and the metadata are opened from the body key.
+    fn open(&self, key: &Key) -> Result<(BodyKeys<'_>, Option<Metadata>), Error> {
+        let (headerdigest, nonce_prefix, commitment) = match (&self.preamble, &self.commitment) {
+            (Preamble::V2(headerdigest, nonce_prefix), Some(commitment)) => {
+                (headerdigest, nonce_prefix, commitment)
+            }
+            _ => return Err(Error::FormatViolation),
+        };
+
+        let schedule = KeySchedule::new(key);
+        if !bool::from(schedule.commitment().ct_eq(commitment)) {
+            return Err(Error::WrongKey);
+        }
+        let (aeadkey, metadatakey) = (schedule.segment_key(headerdigest), schedule.metadata_key());
+
+        let metadata = match &self.metadata {
+            Some(sealed) => Some(open_metadata(&metadatakey, sealed)?),
+            None => None,
+        };
+
+        #[cfg(feature = "signing")]
+        let signed = self
+            .sender
+            .as_ref()
+            .map(|c| SignedBody::new(c.clone(), headerdigest));
+        #[cfg(not(feature = "signing"))]
+        let signed = None;
+
+        Ok((
+            BodyKeys::V2(
+                Segments::new(&aeadkey, nonce_prefix, headerdigest),
+                self.format,
+                signed,
+            ),
+            metadata,
+        ))
+    }
+
+    /// The earliest timestamp of the recipients, before which the PKG does not issue their keys.
+    pub fn earliest_timestamp(&self) -> u64 {
+        self.recipients
+            .iter()
+            .map(|r| r.identity().timestamp)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// The ways in which the stream can be unsealed.
+    pub fn unlock_methods(&self) -> ArrayVec<[UnlockMethod; 3]> {
+        let mut methods = ArrayVec::new();
+
+        match self.recipients.first() {
+            #[cfg(feature = "hybrid")]
+            Some(r) if r.is_hybrid() => methods.push(UnlockMethod::HybridIdentity),
+            Some(_) => methods.push(UnlockMethod::Identity),
+            None => {}
+        }
+
+        #[cfg(feature = "passphrase")]
+        if self.passphrase.is_some() {
+            methods.push(UnlockMethod::Passphrase);
+        }
+
+        #[cfg(feature = "escrow")]
+        if self.escrow.is_some() {
+            methods.push(UnlockMethod::Escrow);
+        }
+
+        methods
+    }
+}
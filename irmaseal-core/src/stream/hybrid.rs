@@ -0,0 +1,144 @@
+//! Hybrid post-quantum mode, in which the body key is additionally encapsulated for every
+//! recipient using the Kyber768 KEM.
+//!
+//! The body key is then wrapped with a key that is derived from both the IBE shared secret and
+//! the Kyber shared secret, such that it stays confidential as long as either of both holds.
+//! Unlike the IBE keys, the Kyber key pair is generated and held by the recipient itself.
+
+use crate::stream::*;
+use crate::*;
+
+use arrayref::array_ref;
+use digest::{Digest, FixedOutput};
+use rand::{CryptoRng, Rng};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::stream::util::Key;
+
+pub(crate) const HYBRID_CIPHERTEXT_SIZE: usize = pqc_kyber::KYBER_CIPHERTEXTBYTES;
+
+/// The post-quantum public key of a recipient, for which the body key is encapsulated in hybrid mode.
+#[derive(Clone)]
+pub struct HybridPublicKey(pqc_kyber::PublicKey);
+
+/// The post-quantum secret key of a recipient, which is required to unseal a stream in hybrid mode.
+///
+/// Is overwritten with zeroes when dropped.
+pub struct HybridSecretKey(pqc_kyber::SecretKey);
+
+/// A Kyber shared secret, which is overwritten with zeroes when dropped.
+type SharedSecret = Zeroizing<[u8; pqc_kyber::KYBER_SSBYTES]>;
+
+impl core::fmt::Debug for HybridPublicKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_tuple("HybridPublicKey").finish()
+    }
+}
+
+/// Adapts an rng of `rand` to the `rand_core` traits that are required by `pqc_kyber`.
+struct RngAdapter<'a, R>(&'a mut R);
+
+impl<R: Rng> rand_core_06::RngCore for RngAdapter<'_, R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core_06::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl<R: Rng + CryptoRng> rand_core_06::CryptoRng for RngAdapter<'_, R> {}
+
+/// Generate a post-quantum key pair for a recipient.
+pub fn generate_hybrid_keys<R: Rng + CryptoRng>(
+    rng: &mut R,
+) -> Result<(HybridPublicKey, HybridSecretKey), Error> {
+    let keys = pqc_kyber::keypair(&mut RngAdapter(rng)).or(Err(Error::ConstraintViolation))?;
+
+    Ok((HybridPublicKey(keys.public), HybridSecretKey(keys.secret)))
+}
+
+impl HybridPublicKey {
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<HybridPublicKey> {
+        if b.len() != pqc_kyber::KYBER_PUBLICKEYBYTES {
+            return None;
+        }
+
+        Some(HybridPublicKey(*array_ref![
+            b,
+            0,
+            pqc_kyber::KYBER_PUBLICKEYBYTES
+        ]))
+    }
+
+    /// Generate a shared secret, and encapsulate it for the holder of the secret key.
+    pub(crate) fn encaps<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> Result<([u8; HYBRID_CIPHERTEXT_SIZE], SharedSecret), Error> {
+        let (c, ss) = pqc_kyber::encapsulate(&self.0, &mut RngAdapter(rng))
+            .or(Err(Error::ConstraintViolation))?;
+        Ok((c, Zeroizing::new(ss)))
+    }
+}
+
+impl HybridSecretKey {
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<HybridSecretKey> {
+        if b.len() != pqc_kyber::KYBER_SECRETKEYBYTES {
+            return None;
+        }
+
+        Some(HybridSecretKey(*array_ref![
+            b,
+            0,
+            pqc_kyber::KYBER_SECRETKEYBYTES
+        ]))
+    }
+
+    /// Decapsulate the shared secret.
+    ///
+    /// Kyber rejects ciphertexts implicitly, hence a foreign ciphertext yields an unrelated
+    /// shared secret, which is only detected by means of the key commitment.
+    pub(crate) fn decaps(&self, c: &[u8]) -> Result<SharedSecret, Error> {
+        let ss = pqc_kyber::decapsulate(c, &self.0).or(Err(Error::FormatViolation))?;
+        Ok(Zeroizing::new(ss))
+    }
+}
+
+impl Drop for HybridSecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Combine the IBE shared secret with the Kyber shared secret and ciphertext,
+/// into the secret with which the body key is wrapped.
+pub(crate) fn combine(ss: &[u8], pq_ss: &[u8], pq_ciphertext: &[u8]) -> Key {
+    let mut h = sha3::Sha3_256::new();
+    h.input(b"IRMAseal hybrid key");
+    h.input(ss);
+    h.input(pq_ss);
+    h.input(pq_ciphertext);
+
+    let mut res = Key::new([0u8; KEYSIZE]);
+    res.copy_from_slice(&h.fixed_result());
+    res
+}
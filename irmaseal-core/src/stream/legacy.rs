@@ -0,0 +1,109 @@
+use crate::stream::*;
+use crate::*;
+
+use crate::stream::util::Key;
+
+use arrayref::array_ref;
+use ctr::stream_cipher::{NewStreamCipher, StreamCipher};
+use hmac::Mac;
+use zeroize::Zeroizing;
+
+/// Body of a format version 0 stream, which is encrypted using AES-CTR and authenticated
+/// using a single HMAC at the end of the stream.
+///
+/// **Warning**: will only validate the authenticity of the plaintext when calling `validate`.
+pub(crate) struct LegacyBody {
+    aes: SymCrypt,
+    hmac: Verifier,
+    resultbuf: Option<Zeroizing<[u8; BLOCKSIZE]>>,
+    resultstart: usize,
+}
+
+impl LegacyBody {
+    /// Reads the IV from the stream, and feeds both the header and the IV to the HMAC.
+    ///
+    /// The keys are derived from the KEM shared secret using `derive_keys`.
+    pub fn new<R: Readable>(
+        (skey, mackey): (Key, Key),
+        headerbuf: &[u8],
+        r: &mut R,
+    ) -> Result<LegacyBody, Error> {
+        let mut hmac = Verifier::new_varkey(&mackey[..]).unwrap();
+        hmac.input(headerbuf);
+
+        let iv = r.read_bytes_strict(IVSIZE)?;
+        let iv: &[u8; IVSIZE] = array_ref![&iv, 0, IVSIZE];
+        hmac.input(iv);
+
+        let aes = SymCrypt::new(&(*skey).into(), &(*iv).into());
+
+        Ok(LegacyBody {
+            aes,
+            hmac,
+            resultbuf: None,
+            resultstart: 0,
+        })
+    }
+
+    /// Read up to `BLOCKSIZE` bytes at a time.
+    pub fn read<R: Readable>(&mut self, r: &mut R) -> Result<&[u8], Error> {
+        let (resultsize, macbuf) = match self.resultbuf.as_mut() {
+            None => (BLOCKSIZE, None),
+            Some(dst) => {
+                let mut macbuf = [0u8; MACSIZE];
+                macbuf.copy_from_slice(&dst[BLOCKSIZE - MACSIZE..BLOCKSIZE]);
+                (BLOCKSIZE - MACSIZE, Some(macbuf))
+            }
+        };
+
+        // TODO eliminate extra check.
+        let dst = self
+            .resultbuf
+            .get_or_insert_with(|| Zeroizing::new([0u8; BLOCKSIZE]));
+        let src = r.read_bytes(resultsize)?;
+        let srcsize = src.len();
+
+        if srcsize == 0 {
+            return Err(Error::EndOfStream);
+        }
+
+        let dstmid = BLOCKSIZE - srcsize;
+        dst[dstmid..BLOCKSIZE].copy_from_slice(src);
+
+        let dststart = match macbuf {
+            None => dstmid,
+            Some(macbuf) => {
+                let dststart = dstmid - MACSIZE;
+                dst[dststart..dstmid].copy_from_slice(&macbuf);
+                dststart
+            }
+        };
+
+        let mut content = &mut dst[dststart..BLOCKSIZE - MACSIZE];
+        self.hmac.input(content);
+        self.aes.decrypt(&mut content);
+        self.resultstart = dststart;
+
+        Ok(content)
+    }
+
+    /// The plaintext that was yielded by the last call to `read`.
+    #[cfg(feature = "std")]
+    pub fn last(&self) -> &[u8] {
+        match &self.resultbuf {
+            None => &[],
+            Some(resultbuf) => &resultbuf[self.resultstart..BLOCKSIZE - MACSIZE],
+        }
+    }
+
+    /// Will check the HMAC once the entire stream is exhausted.
+    pub fn validate(&self) -> bool {
+        match &self.resultbuf {
+            None => false,
+            Some(resultbuf) => {
+                let macbuf = &resultbuf[BLOCKSIZE - MACSIZE..BLOCKSIZE];
+                self.hmac.clone().verify(macbuf).is_ok()
+            }
+        }
+    }
+}
@@ -1,25 +1,102 @@
 //! Implementation of the IRMAseal stream format. Includes zero-allocation streaming encryption and decryption.
 
+#[cfg(feature = "async")]
+mod asynchronous;
+mod compression;
+#[cfg(feature = "escrow")]
+mod escrow;
+mod header;
+#[cfg(feature = "hybrid")]
+mod hybrid;
+mod legacy;
 mod opener;
+#[cfg(feature = "std")]
+mod parallel;
+#[cfg(feature = "passphrase")]
+mod passphrase;
 mod sealer;
+#[cfg(feature = "std")]
+mod seekable;
+mod segment;
+#[cfg(feature = "signing")]
+mod sender;
 pub(crate) mod util;
 
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+pub use compression::Compression;
+#[cfg(feature = "escrow")]
+pub use escrow::{generate_escrow_keys, EscrowPublicKey, EscrowSecretKey};
+pub use header::{Recipient, UnlockMethod};
+#[cfg(feature = "hybrid")]
+pub use hybrid::{generate_hybrid_keys, HybridPublicKey, HybridSecretKey};
 pub use opener::*;
+#[cfg(feature = "std")]
+pub use parallel::{ParallelOpener, ParallelSealer};
+#[cfg(feature = "passphrase")]
+pub use passphrase::{Passphrase, PassphraseCost};
 pub use sealer::*;
+#[cfg(feature = "std")]
+pub use seekable::SeekableOpener;
+#[cfg(feature = "signing")]
+pub use sender::{
+    MasterSigningKey, MasterVerifyingKey, SenderCertificate, SenderKey, SIGNATURE_SIZE,
+};
 
 pub(crate) type SymCrypt = ctr::Ctr128<aes::Aes256>;
 pub(crate) type Verifier = hmac::Hmac<sha3::Sha3_256>;
+pub(crate) type SegmentCipher = aes_gcm::Aes256Gcm;
+pub(crate) type HeaderDigest = sha3::Sha3_256;
 
 /// The tag 'IRMASEAL' with which all IRMAseal bytestreams start.
 pub(crate) const PRELUDE: [u8; 4] = [0x14, 0x8A, 0x8E, 0xA7];
-pub(crate) const FORMAT_VERSION: u8 = 0x00;
+
+/// Single recipient format, in which the body key is derived directly from the IBE ciphertext.
+/// The body is encrypted using AES-CTR and authenticated with a single HMAC.
+pub(crate) const FORMAT_VERSION_V0: u8 = 0x00;
+/// Multi recipient format, in which a random body key is wrapped for every recipient.
+/// The header consists of typed and length-prefixed fields, which are all authenticated.
+/// The body is encrypted in segments using AES-GCM, each of which is authenticated separately.
+/// All keys are derived from the body key using a key schedule that is bound to the format version
+/// and the header, and the header commits to the body key.
+pub(crate) const FORMAT_VERSION_V2: u8 = 0x02;
+/// The format version that is written by the `Sealer`.
+pub const FORMAT_VERSION: u8 = FORMAT_VERSION_V2;
 
 pub(crate) const KEYSIZE: usize = 32;
 pub(crate) const IVSIZE: usize = 16;
 pub(crate) const MACSIZE: usize = 32;
+pub(crate) const NONCESIZE: usize = 12;
+pub(crate) const NONCE_PREFIX_SIZE: usize = NONCESIZE - 5;
+pub(crate) const TAGSIZE: usize = 16;
+pub(crate) const DIGESTSIZE: usize = 32;
+/// The maximum size of the KEM ciphertext of a single recipient.
+pub(crate) const MAX_CIPHERTEXT_SIZE: usize = 512;
+pub(crate) const HINTSIZE: usize = 2;
+
+/// The maximum number of recipients a single IRMAseal bytestream can be sealed for.
+pub const MAX_RECIPIENTS: usize = 16;
 
-/// The stack buffer size that `opener` and `sealer` will use to yield chunks of plaintext and ciphertext.
+/// The default stack buffer size that `opener` and `sealer` will use to yield chunks of plaintext and ciphertext.
+///
+/// Also the default size of the plaintext of every segment, except for the final one, which may be shorter.
+/// Streams with another block size record it in their header.
 pub const BLOCKSIZE: usize = 512;
+
+/// The largest block size that can be recorded in a header.
+pub const MAX_BLOCKSIZE: usize = 1 << 20;
+
+/// The size of a segment of ciphertext with the default block size, including its authentication tag.
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+pub(crate) const SEGMENTSIZE: usize = BLOCKSIZE + TAGSIZE;
+
+/// Whether plaintext can be divided into segments of `block_size` bytes, including any padding trailer.
+pub(crate) fn valid_block_size(block_size: usize) -> bool {
+    block_size > PADDING_TRAILERSIZE && block_size <= MAX_BLOCKSIZE
+}
+
+/// The size of the trailer of a padded segment, which contains the length of its content.
+pub(crate) const PADDING_TRAILERSIZE: usize = 4;
@@ -1,138 +1,632 @@
-use crate::stream::util::ArchiveReader;
+use crate::kem::Kem;
+#[cfg(feature = "compression")]
+use crate::stream::compression::Decompressor;
+use crate::stream::header::{BodyFormat, BodyKeys, Header, HybridSecretKey, SignedBody};
+use crate::stream::legacy::LegacyBody;
+use crate::stream::segment::Segments;
 use crate::stream::*;
+#[cfg(feature = "std")]
+use crate::util::IoReader;
 use crate::*;
 
-use arrayref::array_ref;
-use ctr::stream_cipher::{NewStreamCipher, StreamCipher};
-use hmac::Mac;
+use arrayvec::ArrayVec;
+use zeroize::Zeroizing;
 
 /// First stage opener of an IRMAseal encrypted bytestream.
-/// It reads the IRMAseal header, and yields the recipient Identity for which the content is intended.
+/// It reads the IRMAseal header, and yields the recipient Identities for which the content is intended.
 ///
-/// Enables the library user to lookup the UserSecretKey corresponding to this Identity before continuing.
-pub struct OpenerSealed<R: Readable> {
-    ar: ArchiveReader<R, [u8; 2048]>,
+/// Enables the library user to lookup the UserSecretKey corresponding to one of these Identities before continuing.
+///
+/// The block size of the stream is read from the header. With `std`, the body is read using a buffer
+/// that is allocated to that block size, up to `MAX_BLOCKSIZE`. Otherwise the block size can be
+/// at most `B` bytes, and a larger buffer can be chosen using `with_block_size`.
+pub struct OpenerSealed<R: Readable, const B: usize = BLOCKSIZE> {
+    header: Header,
+    r: R,
 }
 
 /// Second stage opener of an IRMAseal encrypted bytestream.
 ///
-/// **Warning**: will only validate the authenticity of the plaintext when calling `validate`.
-pub struct OpenerUnsealed<R: Readable> {
-    aes: SymCrypt,
-    hmac: Verifier,
+/// For format version 2 streams, every segment is authenticated before it is yielded by `read`.
+///
+/// **Warning**: for format version 0 streams, will only validate the authenticity of the plaintext when calling `validate`.
+pub struct OpenerUnsealed<R: Readable, const B: usize = BLOCKSIZE> {
+    body: Body<B>,
+    metadata: Option<Metadata>,
     r: R,
-    resultbuf: Option<[u8; BLOCKSIZE]>,
+    /// The number of bytes of the last plaintext chunk that were consumed through `std::io::Read`.
+    #[cfg(feature = "std")]
+    offset: usize,
 }
 
-impl<R: Readable> OpenerSealed<R> {
-    /// Starts interpreting a bytestream as an IRMAseal stream.
-    /// Will immediately detect whether the bytestream actually is such a stream, and will yield
-    /// the identity for which the stream is intended, as well as the stream continuation.
-    pub fn new(r: R) -> Result<(Identity, OpenerSealed<R>), Error> {
-        let mut ar = ArchiveReader::<R, [u8; 2048]>::new(r);
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Body<const B: usize = BLOCKSIZE> {
+    V0(LegacyBody),
+    V2(SegmentedBody<B>),
+}
 
-        let prelude = ar.read_bytes_strict(PRELUDE.len())?;
-        if prelude != PRELUDE {
-            return Err(Error::NotIRMASEAL);
+/// The length of the content of a segment that was just opened, excluding its tag.
+///
+/// Throws a FormatViolation if the trailer of a padded segment is malformed.
+pub(crate) fn content_len(segment: &[u8], format: &BodyFormat) -> Result<usize, Error> {
+    if !format.padded {
+        return Ok(segment.len());
+    }
+
+    if segment.len() != format.block_size {
+        return Err(Error::FormatViolation);
+    }
+
+    let capacity = format.block_size - PADDING_TRAILERSIZE;
+    let len = u32::from_be_bytes(*arrayref::array_ref![
+        segment,
+        capacity,
+        PADDING_TRAILERSIZE
+    ]);
+
+    match len as usize {
+        len if len <= capacity => Ok(len),
+        _ => Err(Error::FormatViolation),
+    }
+}
+
+/// Body of a format version 2 stream, which consists of separately authenticated segments.
+pub(crate) struct SegmentedBody<const B: usize> {
+    segments: Segments,
+    format: BodyFormat,
+    /// Contains the content of a single segment, and is allocated to the block size of the stream.
+    #[cfg(feature = "std")]
+    buf: Zeroizing<std::vec::Vec<u8>>,
+    /// Contains the content of a single segment.
+    #[cfg(not(feature = "std"))]
+    buf: Zeroizing<[u8; B]>,
+    /// A single byte of the next segment, to determine whether the current segment is the final one.
+    next: Option<u8>,
+    /// The length of the content of the last segment, which excludes any padding.
+    content: usize,
+    finished: bool,
+    failed: bool,
+    /// Verifies the signature that trails the body of a signed stream.
+    #[cfg_attr(not(feature = "signing"), allow(dead_code))]
+    signed: Option<SignedBody>,
+    #[cfg(feature = "compression")]
+    decompressor: Option<Decompressor>,
+    /// The number of bytes of the content of the last segment that have been decompressed.
+    #[cfg(feature = "compression")]
+    decompressed: usize,
+}
+
+impl<const B: usize> SegmentedBody<B> {
+    /// Throws a ConstraintViolation if the block size of the stream exceeds `B`, without `std`.
+    fn new(
+        segments: Segments,
+        format: BodyFormat,
+        signed: Option<SignedBody>,
+    ) -> Result<SegmentedBody<B>, Error> {
+        #[cfg(not(feature = "std"))]
+        if format.block_size > B {
+            return Err(Error::ConstraintViolation);
         }
 
-        let format_version = ar.read_byte()?;
-        if format_version != FORMAT_VERSION {
-            return Err(Error::IncorrectVersion);
+        Ok(SegmentedBody {
+            segments,
+            format,
+            // The block size is at most `MAX_BLOCKSIZE`, as the header is valid.
+            #[cfg(feature = "std")]
+            buf: Zeroizing::new(std::vec![0u8; format.block_size]),
+            #[cfg(not(feature = "std"))]
+            buf: Zeroizing::new([0u8; B]),
+            next: None,
+            content: 0,
+            finished: false,
+            failed: false,
+            signed,
+            #[cfg(feature = "compression")]
+            decompressor: Decompressor::new(format.compression),
+            #[cfg(feature = "compression")]
+            decompressed: 0,
+        })
+    }
+
+    fn read<R: Readable>(&mut self, r: &mut R) -> Result<&[u8], Error> {
+        #[cfg(feature = "compression")]
+        {
+            if self.decompressor.is_some() {
+                return self.read_decompressed(r);
+            }
         }
 
-        let i = Identity::read_from(&mut ar)?;
+        self.read_segment(r)
+    }
+
+    /// Decompress the content of the segments, reading the next segment once the last one is exhausted.
+    ///
+    /// Throws a FormatViolation if the compressed data is malformed, or ends prematurely.
+    #[cfg(feature = "compression")]
+    fn read_decompressed<R: Readable>(&mut self, r: &mut R) -> Result<&[u8], Error> {
+        loop {
+            let d = match &mut self.decompressor {
+                Some(d) => d,
+                None => return Err(Error::ConstraintViolation),
+            };
+
+            let input = &self.buf[self.decompressed..self.content];
+            let consumed = d.decompress(input)?;
+            self.decompressed += consumed;
 
-        Ok((i, OpenerSealed { ar }))
+            if !d.last().is_empty() {
+                break;
+            }
+
+            if consumed == 0 {
+                if self.decompressed < self.content {
+                    return Err(Error::FormatViolation);
+                }
+
+                match self.read_segment(r) {
+                    Ok(_) => self.decompressed = 0,
+                    Err(Error::EndOfStream) => match &self.decompressor {
+                        Some(d) if d.is_finished() => return Err(Error::EndOfStream),
+                        _ => return Err(Error::FormatViolation),
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Ok(self.last())
     }
 
-    /// Will unseal the stream continuation and yield a plaintext bytestream.
-    pub fn unseal(mut self, usk: &UserSecretKey) -> Result<OpenerUnsealed<R>, Error> {
-        const CIPHERTEXT_SIZE: usize = 144;
+    /// Read and open the next segment, and yield its content.
+    fn read_segment<R: Readable>(&mut self, r: &mut R) -> Result<&[u8], Error> {
+        if self.finished {
+            return Err(Error::EndOfStream);
+        }
 
-        let cbuf = self.ar.read_bytes_strict(CIPHERTEXT_SIZE)?;
-        let c = crate::util::open_ct(ibe::kiltz_vahlis_one::CipherText::from_bytes(array_ref![
-            cbuf,
-            0,
-            CIPHERTEXT_SIZE
-        ]))
-        .ok_or(Error::FormatViolation)?;
+        if self.failed {
+            return Err(Error::IntegrityViolation);
+        }
 
-        let m = ibe::kiltz_vahlis_one::decrypt(&usk.0, &c);
-        let (skey, mackey) = crate::stream::util::derive_keys(&m);
+        // The signature that trails a signed stream is held back.
+        #[cfg(feature = "signing")]
+        let signed = &mut self.signed;
+        #[cfg_attr(not(feature = "signing"), allow(unused_mut))]
+        let mut read_fill = |r: &mut R, buf: &mut [u8]| {
+            #[cfg(feature = "signing")]
+            if let Some(signed) = signed {
+                return signed.read_fill(r, buf);
+            }
 
-        let mut hmac = Verifier::new_varkey(&mackey).unwrap();
+            crate::stream::util::read_fill(r, buf)
+        };
 
-        let (headerbuf, mut r) = self.ar.disclose();
-        hmac.input(&headerbuf);
+        let block_size = self.format.block_size;
+        let mut len = 0;
+        if let Some(b) = self.next.take() {
+            self.buf[0] = b;
+            len = 1;
+        }
 
-        let iv = r.read_bytes_strict(IVSIZE)?;
-        let iv: &[u8; IVSIZE] = array_ref![&iv, 0, IVSIZE];
-        hmac.input(iv);
+        len += read_fill(r, &mut self.buf[len..block_size])?;
 
-        let aes = SymCrypt::new(&skey.into(), &(*iv).into());
+        let mut tag = [0u8; TAGSIZE];
+        let taglen = if len == block_size {
+            read_fill(r, &mut tag)?
+        } else {
+            0
+        };
 
-        Ok(OpenerUnsealed {
-            aes,
-            hmac,
-            r,
-            resultbuf: None,
-        })
+        let mut next = [0u8; 1];
+        let last = taglen < TAGSIZE || read_fill(r, &mut next)? == 0;
+        if !last {
+            self.next = Some(next[0]);
+        }
+
+        let segmentlen = len + taglen;
+        if segmentlen < TAGSIZE {
+            return Err(Error::PrematureEndError);
+        }
+
+        #[cfg(feature = "signing")]
+        if let Some(signed) = &mut self.signed {
+            signed.input(&self.buf[..len]);
+            signed.input(&tag[..taglen]);
+        }
+
+        // The tag of a short final segment starts within the buffer.
+        let contentlen = segmentlen - TAGSIZE;
+        if taglen < TAGSIZE {
+            tag.copy_within(..taglen, TAGSIZE - taglen);
+            tag[..TAGSIZE - taglen].copy_from_slice(&self.buf[contentlen..len]);
+        }
+
+        let format = self.format;
+        let content = &mut self.buf[..contentlen];
+        let opened = self
+            .segments
+            .open(content, &tag, last)
+            .and_then(|_| content_len(content, &format));
+
+        self.content = match opened {
+            Ok(content) => content,
+            Err(e) => {
+                self.failed = true;
+                return Err(e);
+            }
+        };
+
+        // The final segment is only released once the signature turns out to be authentic.
+        #[cfg(feature = "signing")]
+        if let Some(signed) = &mut self.signed {
+            if last && !signed.verify() {
+                self.failed = true;
+                return Err(Error::IntegrityViolation);
+            }
+        }
+
+        self.finished = last;
+
+        Ok(&self.buf[..self.content])
+    }
+
+    /// Whether the final segment has been opened, and all compressed data has been decompressed.
+    fn is_valid(&self) -> bool {
+        #[cfg(feature = "compression")]
+        {
+            if let Some(d) = &self.decompressor {
+                return self.finished && d.is_finished();
+            }
+        }
+
+        self.finished
+    }
+
+    /// The certificate of the sender, once the entire stream is read and its signature is verified.
+    #[cfg(feature = "signing")]
+    fn sender(&self) -> Option<&SenderCertificate> {
+        self.signed
+            .as_ref()
+            .and_then(|s| s.sender())
+            .filter(|_| self.is_valid())
+    }
+
+    /// The plaintext that was yielded by the last call to `read`.
+    #[cfg(feature = "std")]
+    fn last(&self) -> &[u8] {
+        #[cfg(feature = "compression")]
+        {
+            if let Some(d) = &self.decompressor {
+                return d.last();
+            }
+        }
+
+        &self.buf[..self.content]
     }
 }
 
-impl<R: Readable> OpenerUnsealed<R> {
-    /// Read up to `BLOCKSIZE` bytes at a time.
-    pub fn read(&mut self) -> Result<&[u8], Error> {
-        let (resultsize, macbuf) = match self.resultbuf.as_mut() {
-            None => (BLOCKSIZE, None),
-            Some(dst) => {
-                let mut macbuf = [0u8; MACSIZE];
-                macbuf.copy_from_slice(&dst[BLOCKSIZE - MACSIZE..BLOCKSIZE]);
-                (BLOCKSIZE - MACSIZE, Some(macbuf))
+impl<const B: usize> Body<B> {
+    /// Start reading the body, using the keys that were unsealed from the header.
+    ///
+    /// Throws a ConstraintViolation if the block size of the stream exceeds `B`, without `std`.
+    pub fn new<R: Readable>(keys: BodyKeys, r: &mut R) -> Result<Body<B>, Error> {
+        match keys {
+            BodyKeys::V0(keys, headerbuf) => Ok(Body::V0(LegacyBody::new(keys, headerbuf, r)?)),
+            BodyKeys::V2(segments, format, signed) => {
+                Ok(Body::V2(SegmentedBody::new(segments, format, signed)?))
             }
-        };
+        }
+    }
 
-        // TODO eliminate extra check.
-        let dst = self.resultbuf.get_or_insert_with(|| [0u8; BLOCKSIZE]);
-        let src = self.r.read_bytes(resultsize)?;
-        let srcsize = src.len();
+    pub fn read<R: Readable>(&mut self, r: &mut R) -> Result<&[u8], Error> {
+        match self {
+            Body::V0(body) => body.read(r),
+            Body::V2(body) => body.read(r),
+        }
+    }
 
-        if srcsize == 0 {
-            return Err(Error::EndOfStream);
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Body::V0(body) => body.validate(),
+            Body::V2(body) => body.is_valid(),
         }
+    }
 
-        let dstmid = BLOCKSIZE - srcsize;
-        dst[dstmid..BLOCKSIZE].copy_from_slice(src);
+    /// The certificate of the sender, once the entire stream is read and its signature is verified.
+    #[cfg(feature = "signing")]
+    pub fn sender(&self) -> Option<&SenderCertificate> {
+        match self {
+            Body::V0(_) => None,
+            Body::V2(body) => body.sender(),
+        }
+    }
 
-        let dststart = match macbuf {
-            None => dstmid,
-            Some(macbuf) => {
-                let dststart = dstmid - MACSIZE;
-                dst[dststart..dstmid].copy_from_slice(&macbuf);
-                dststart
+    /// The plaintext that was yielded by the last call to `read`.
+    #[cfg(feature = "std")]
+    pub fn last(&self) -> &[u8] {
+        match self {
+            Body::V0(body) => body.last(),
+            Body::V2(body) => body.last(),
+        }
+    }
+}
+
+impl<R: Readable> OpenerSealed<R> {
+    /// Starts interpreting a bytestream as an IRMAseal stream.
+    /// Will immediately detect whether the bytestream actually is such a stream, and will yield
+    /// the stream continuation, which lists the recipients for which the stream is intended.
+    pub fn new(r: R) -> Result<OpenerSealed<R>, Error> {
+        Self::with_block_size(r)
+    }
+}
+
+impl<R: Readable, const B: usize> OpenerSealed<R, B> {
+    /// Starts interpreting a bytestream as an IRMAseal stream, of which the body is read
+    /// using a buffer of `B` bytes.
+    ///
+    /// For example `OpenerSealed::<_, 65536>::with_block_size(..)` opens streams with a block size of up to 64 KiB.
+    /// Has no effect with `std`, as the buffer is then allocated to the block size of the stream.
+    pub fn with_block_size(r: R) -> Result<OpenerSealed<R, B>, Error> {
+        let (header, r) = Header::read_from(r)?;
+        Ok(OpenerSealed { header, r })
+    }
+
+    /// The recipients for which the stream is intended.
+    pub fn recipients(&self) -> &[Recipient] {
+        &self.header.recipients
+    }
+
+    /// The earliest timestamp of the identities of the recipients, as a UNIX timestamp.
+    ///
+    /// The PKG only issues the key of an identity once its timestamp has passed. The header does
+    /// not record when the stream was sealed, hence this does not tell whether the stream was
+    /// sealed for a future timestamp.
+    pub fn earliest_timestamp(&self) -> u64 {
+        self.header.earliest_timestamp()
+    }
+
+    /// The policy with which the timestamps of the recipients are rounded.
+    pub fn timestamp_policy(&self) -> TimestampPolicy {
+        self.header.timestamp_policy
+    }
+
+    /// The ways in which the stream can be unsealed.
+    pub fn unlock_methods(&self) -> ArrayVec<[UnlockMethod; 3]> {
+        self.header.unlock_methods()
+    }
+
+    /// The escrow key of the organisation that can recover the stream, if any.
+    #[cfg(feature = "escrow")]
+    pub fn escrow(&self) -> Option<&EscrowPublicKey> {
+        self.header.escrow()
+    }
+
+    /// Whether the stream is signed by its sender, whose identity is yielded once the stream is read.
+    #[cfg(feature = "signing")]
+    pub fn is_signed(&self) -> bool {
+        self.header.sender.is_some()
+    }
+
+    /// The size of the plaintext of every segment, which has to fit in `B` to unseal the stream without `std`.
+    pub fn block_size(&self) -> usize {
+        self.header.format.block_size
+    }
+
+    /// Will unseal the stream continuation for one of the recipients and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when none of the `recipients` matches the identity,
+    /// an IncorrectScheme error when the stream was sealed using another KEM or in hybrid mode,
+    /// and a ConstraintViolation error when the block size of the stream exceeds `B` without `std`.
+    ///
+    /// Throws a WrongKey error before any of the body is read when `usk` is not the key of the identity,
+    /// unless the stream predates format version 2, in which case only `validate` will fail.
+    pub fn unseal<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+    ) -> Result<OpenerUnsealed<R, B>, Error> {
+        self.unseal_with(identity, usk, None)
+    }
+
+    /// Will unseal the stream continuation for one of the recipients in hybrid mode.
+    ///
+    /// Throws an IncorrectScheme error when the stream was not sealed in hybrid mode.
+    #[cfg(feature = "hybrid")]
+    pub fn unseal_hybrid<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hsk: &HybridSecretKey,
+    ) -> Result<OpenerUnsealed<R, B>, Error> {
+        self.unseal_with(identity, usk, Some(hsk))
+    }
+
+    fn unseal_with<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+    ) -> Result<OpenerUnsealed<R, B>, Error> {
+        let OpenerSealed { header, mut r } = self;
+        let (keys, metadata) = header.unseal(identity, usk, hybrid)?;
+        let body = Body::new(keys, &mut r)?;
+
+        Ok(OpenerUnsealed::new(body, metadata, r))
+    }
+
+    /// Will unseal the stream continuation using the passphrase, and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed for a passphrase,
+    /// and a WrongKey error when the passphrase is not correct.
+    #[cfg(feature = "passphrase")]
+    pub fn unseal_with_passphrase(self, passphrase: &str) -> Result<OpenerUnsealed<R, B>, Error> {
+        let OpenerSealed { header, mut r } = self;
+        let (keys, metadata) = header.unseal_passphrase(passphrase)?;
+        let body = Body::new(keys, &mut r)?;
+
+        Ok(OpenerUnsealed::new(body, metadata, r))
+    }
+
+    /// Will unseal the stream continuation using the escrow secret key, and yield a plaintext bytestream.
+    ///
+    /// Throws an UnknownRecipient error when the stream was not sealed in escrow mode,
+    /// and a WrongKey error when it was sealed for another escrow key.
+    #[cfg(feature = "escrow")]
+    pub fn unseal_with_escrow(self, sk: &EscrowSecretKey) -> Result<OpenerUnsealed<R, B>, Error> {
+        let OpenerSealed { header, mut r } = self;
+        let (keys, metadata) = header.unseal_escrow(sk)?;
+        let body = Body::new(keys, &mut r)?;
+
+        Ok(OpenerUnsealed::new(body, metadata, r))
+    }
+
+    /// Will unseal the stream continuation for one of the recipients, and yield a plaintext
+    /// bytestream of which the segments are opened across `threads` threads.
+    ///
+    /// Throws an IncorrectVersion error for format version 0 streams, and a ConstraintViolation
+    /// error when `threads` is zero or the stream is compressed or signed.
+    #[cfg(feature = "std")]
+    pub fn unseal_parallel<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        threads: usize,
+    ) -> Result<ParallelOpener<R>, Error> {
+        self.unseal_parallel_with(identity, usk, None, threads)
+    }
+
+    /// Will unseal the stream continuation for one of the recipients in hybrid mode, and yield
+    /// a plaintext bytestream of which the segments are opened across `threads` threads.
+    #[cfg(feature = "hybrid")]
+    pub fn unseal_hybrid_parallel<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hsk: &HybridSecretKey,
+        threads: usize,
+    ) -> Result<ParallelOpener<R>, Error> {
+        self.unseal_parallel_with(identity, usk, Some(hsk), threads)
+    }
+
+    #[cfg(feature = "std")]
+    fn unseal_parallel_with<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+        threads: usize,
+    ) -> Result<ParallelOpener<R>, Error> {
+        let OpenerSealed { header, r } = self;
+
+        match header.unseal(identity, usk, hybrid)? {
+            (BodyKeys::V2(segments, format, signed), metadata) if signed.is_none() => {
+                ParallelOpener::new(segments, format, metadata, threads, r)
             }
-        };
+            (BodyKeys::V2(..), _) => Err(Error::ConstraintViolation),
+            (BodyKeys::V0(..), _) => Err(Error::IncorrectVersion),
+        }
+    }
+}
 
-        let mut content = &mut dst[dststart..BLOCKSIZE - MACSIZE];
-        self.hmac.input(content);
-        self.aes.decrypt(&mut content);
+#[cfg(feature = "std")]
+impl<S: std::io::Read + std::io::Seek, const B: usize> OpenerSealed<IoReader<S>, B> {
+    /// Will unseal the stream continuation for one of the recipients, and yield a plaintext
+    /// bytestream that supports random access.
+    ///
+    /// Throws an IncorrectVersion error for format version 0 streams,
+    /// and a ConstraintViolation error for streams that are padded, compressed or signed.
+    pub fn unseal_seekable<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+    ) -> Result<SeekableOpener<S>, Error> {
+        self.unseal_seekable_with(identity, usk, None)
+    }
 
-        Ok(content)
+    /// Will unseal the stream continuation for one of the recipients in hybrid mode,
+    /// and yield a plaintext bytestream that supports random access.
+    #[cfg(feature = "hybrid")]
+    pub fn unseal_hybrid_seekable<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hsk: &HybridSecretKey,
+    ) -> Result<SeekableOpener<S>, Error> {
+        self.unseal_seekable_with(identity, usk, Some(hsk))
     }
 
-    /// Will check the HMAC once the entire stream is exhausted.
-    /// Will only yield the correct value once the **entire** stream is read
-    /// using `write_to`, or by manually calling `write` until `Error::EndOfStream` is yielded.
-    pub fn validate(self) -> bool {
-        match self.resultbuf {
-            None => false,
-            Some(resultbuf) => {
-                let macbuf = &resultbuf[BLOCKSIZE - MACSIZE..BLOCKSIZE];
-                self.hmac.verify(macbuf).is_ok()
+    fn unseal_seekable_with<K: Kem>(
+        self,
+        identity: &Identity,
+        usk: &UserSecretKey<K>,
+        hybrid: Option<&HybridSecretKey>,
+    ) -> Result<SeekableOpener<S>, Error> {
+        let OpenerSealed { header, r } = self;
+
+        match header.unseal(identity, usk, hybrid)? {
+            (BodyKeys::V2(segments, format, signed), metadata) if signed.is_none() => {
+                // The stream may be embedded in the reader, such that the body starts right after the header.
+                let mut r = r.into_inner();
+                let start = r.stream_position()?;
+                SeekableOpener::new(segments, format, metadata, r, start)
             }
+            (BodyKeys::V2(..), _) => Err(Error::ConstraintViolation),
+            (BodyKeys::V0(..), _) => Err(Error::IncorrectVersion),
         }
     }
+}
+
+impl<R: Readable, const B: usize> OpenerUnsealed<R, B> {
+    fn new(body: Body<B>, metadata: Option<Metadata>, r: R) -> OpenerUnsealed<R, B> {
+        OpenerUnsealed {
+            body,
+            metadata,
+            r,
+            #[cfg(feature = "std")]
+            offset: 0,
+        }
+    }
+
+    /// The metadata that was sealed along with the content, if any.
+    ///
+    /// Is available before reading the body. Format version 0 streams never contain metadata.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Read up to a single block of plaintext at a time.
+    ///
+    /// Yields an empty slice for segments that only consist of padding.
+    /// Throws an IntegrityViolation if a segment is not authentic, or the stream has been truncated.
+    pub fn read(&mut self) -> Result<&[u8], Error> {
+        self.body.read(&mut self.r)
+    }
+
+    /// Will check whether the entire stream was authentic once it is exhausted.
+    /// Will only yield the correct value once the **entire** stream is read
+    /// using `write_to`, or by manually calling `read` until `Error::EndOfStream` is yielded.
+    ///
+    /// Consumes the opener, hence the `sender` of a signed stream has to be queried beforehand.
+    pub fn validate(self) -> bool {
+        self.is_valid()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.body.is_valid()
+    }
+
+    /// The identity of the sender that signed the stream, if the PKG with the verifying key
+    /// certified it.
+    ///
+    /// Will only yield the sender once the **entire** stream is read and turned out to be
+    /// authentic, as for `validate`. Streams that are not signed never yield a sender.
+    /// Has to be queried after reading, but before `validate` consumes the opener.
+    #[cfg(feature = "signing")]
+    pub fn sender(&self, mvk: &MasterVerifyingKey) -> Option<&Identity> {
+        self.body
+            .sender()
+            .filter(|c| c.is_issued_by(mvk))
+            .map(|c| c.identity())
+    }
 
     /// Will block and write the entire stream to the argument writer.
     pub fn write_to<W: Writable>(&mut self, w: &mut W) -> Result<(), Error> {
@@ -144,4 +638,49 @@ impl<R: Readable> OpenerUnsealed<R> {
             };
         }
     }
+
+    /// Will block and seal the entire stream again using the argument sealer, such that it
+    /// can be opened by the recipients of the sealer instead, without ever yielding the plaintext.
+    ///
+    /// The sealer is only finished once the entire stream turned out to be authentic. Otherwise
+    /// the stream it has written so far is never finished, and thus never validates, and an
+    /// IntegrityViolation is thrown.
+    pub fn reseal<W: Writable, const S: usize>(
+        mut self,
+        mut sealer: Sealer<'_, W, S>,
+    ) -> Result<(W, u64), Error> {
+        self.write_to(&mut sealer)?;
+
+        if !self.is_valid() {
+            return Err(Error::IntegrityViolation);
+        }
+
+        sealer.finish()
+    }
+}
+
+/// Yields the plaintext, and fails with `ErrorKind::InvalidData` once it turns out not to be authentic.
+///
+/// **Warning**: for format version 0 streams, the plaintext can only be validated at the end of the stream.
+#[cfg(feature = "std")]
+impl<R: Readable, const B: usize> std::io::Read for OpenerUnsealed<R, B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let last = self.body.last();
+
+            if self.offset < last.len() {
+                let n = core::cmp::min(buf.len(), last.len() - self.offset);
+                buf[..n].copy_from_slice(&last[self.offset..self.offset + n]);
+                self.offset += n;
+                return Ok(n);
+            }
+
+            match OpenerUnsealed::read(self).map(|_| ()) {
+                Ok(()) => self.offset = 0,
+                Err(Error::EndOfStream) if self.is_valid() => return Ok(0),
+                Err(Error::EndOfStream) => return Err(Error::IntegrityViolation.into()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 }
@@ -0,0 +1,465 @@
+//! Sealing and opening of large bytestreams, of which the segments are processed across threads.
+//!
+//! Every segment is sealed with a nonce that only depends on its index, hence a batch of segments
+//! can be sealed or opened independently. The resulting bytestream is laid out exactly as by the
+//! `Sealer`, and can be opened by either opener.
+
+use std::convert::TryFrom;
+#[cfg(not(feature = "signing"))]
+use std::marker::PhantomData;
+use std::vec::Vec;
+
+use rand::{CryptoRng, Rng};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::kem::Kem;
+use crate::stream::header::{BodyFormat, Header};
+use crate::stream::opener::content_len;
+use crate::stream::sealer::pad_segment;
+use crate::stream::segment::Segments;
+#[cfg(feature = "signing")]
+use crate::stream::sender::BodySigner;
+use crate::stream::util::CountingWriter;
+use crate::stream::*;
+use crate::*;
+
+/// The number of bytes of sealed segments that every thread processes per batch.
+///
+/// Fresh threads are spawned for every batch, hence a batch has to take considerably longer
+/// to seal than spawning and joining a thread, even with hardware accelerated AES.
+const BYTES_PER_THREAD: usize = 1024 * 1024;
+
+/// The number of segments of `segment_size` bytes that every thread processes per batch.
+fn segments_per_thread(segment_size: usize) -> usize {
+    core::cmp::max(1, BYTES_PER_THREAD / segment_size)
+}
+
+/// Apply `f` to every segment of `buf` along with its index in `buf`, divided over `threads` threads.
+///
+/// Every segment is `segment_size` bytes, except for the last one, which may be shorter.
+fn for_each_segment<T, F>(buf: &mut [u8], segment_size: usize, threads: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &mut [u8]) -> T + Sync,
+{
+    let count = buf.len().div_ceil(segment_size);
+    let per_thread = core::cmp::max(1, count.div_ceil(threads));
+
+    if per_thread >= count {
+        return buf
+            .chunks_mut(segment_size)
+            .enumerate()
+            .map(|(k, segment)| f(k, segment))
+            .collect();
+    }
+
+    std::thread::scope(|s| {
+        let f = &f;
+        let handles: Vec<_> = buf
+            .chunks_mut(per_thread * segment_size)
+            .enumerate()
+            .map(|(t, chunk)| {
+                s.spawn(move || {
+                    chunk
+                        .chunks_mut(segment_size)
+                        .enumerate()
+                        .map(|(k, segment)| f(t * per_thread + k, segment))
+                        .collect::<Vec<T>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("segment thread panicked"))
+            .collect()
+    })
+}
+
+/// The nonce index of the `k`-th segment of a batch that starts at segment `first`.
+fn segment_index(first: u64, k: usize) -> Result<u32, Error> {
+    let index = first
+        .checked_add(k as u64)
+        .ok_or(Error::ConstraintViolation)?;
+    u32::try_from(index).or(Err(Error::ConstraintViolation))
+}
+
+/// Sealer for a bytestream, which seals batches of segments across several threads.
+///
+/// Yields exactly the same bytestream as the `Sealer` with the same block size for the same `rng`,
+/// but buffers about 1 MiB of plaintext per thread at a time. Does not support compression,
+/// as the compressed content of a segment depends on all content that precedes it.
+pub struct ParallelSealer<'a, W: Writable, const B: usize = BLOCKSIZE> {
+    segments: Segments,
+    threads: usize,
+    /// The plaintext of the current batch.
+    buf: Zeroizing<Vec<u8>>,
+    /// The ciphertext of the current batch.
+    out: Zeroizing<Vec<u8>>,
+    /// The number of segments that have been written.
+    written: u64,
+    padding: Padding,
+    /// The number of random padding segments, determined in advance as `finish` has no access to an rng.
+    random: u64,
+    #[cfg(feature = "signing")]
+    signer: Option<BodySigner<'a>>,
+    /// Without signed mode, no key of the options is borrowed.
+    #[cfg(not(feature = "signing"))]
+    signer: PhantomData<&'a SealOptions>,
+    w: CountingWriter<W>,
+}
+
+impl<'a, W: Writable> ParallelSealer<'a, W> {
+    /// Seal a bytestream for several recipients at once, using `threads` threads.
+    ///
+    /// Throws a ConstraintViolation when `threads` is zero, or compression is enabled.
+    pub fn new<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        options: &'a SealOptions,
+        threads: usize,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<ParallelSealer<'a, W>, Error> {
+        Self::with_block_size(recipients, options, threads, pk, rng, w)
+    }
+}
+
+impl<'a, W: Writable, const B: usize> ParallelSealer<'a, W, B> {
+    /// Seal a bytestream for several recipients at once, in segments of `B` bytes of plaintext,
+    /// using `threads` threads.
+    ///
+    /// Throws a ConstraintViolation when `threads` is zero, compression is enabled, or `B` is invalid.
+    pub fn with_block_size<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        options: &'a SealOptions,
+        threads: usize,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<ParallelSealer<'a, W, B>, Error> {
+        if threads == 0 || options.compression != Compression::None {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let mut w = CountingWriter::new(w);
+        let segments = Header::write_to(recipients, options, B, pk, rng, &mut w)?;
+        let random = options.padding.random(rng, B);
+        #[cfg(feature = "signing")]
+        let signer = options
+            .sender
+            .as_ref()
+            .map(|key| BodySigner::new(key, segments.aad()));
+        #[cfg(not(feature = "signing"))]
+        let signer = PhantomData;
+
+        Ok(ParallelSealer {
+            segments,
+            threads,
+            buf: Zeroizing::new(Vec::new()),
+            out: Zeroizing::new(Vec::new()),
+            written: 0,
+            padding: options.padding,
+            random,
+            signer,
+            w,
+        })
+    }
+
+    /// The size of the plaintext of a full batch.
+    fn batch_size(&self) -> usize {
+        self.threads * segments_per_thread(B + TAGSIZE) * self.padding.capacity(B)
+    }
+
+    /// Seal the buffered plaintext, and write it.
+    ///
+    /// If `finish` is set, the batch ends with the final segment, followed by any padding segments.
+    fn write_batch(&mut self, finish: bool) -> Result<(), Error> {
+        let capacity = self.padding.capacity(B);
+        let padded = self.padding != Padding::None;
+
+        let content = core::cmp::max(1, self.buf.len().div_ceil(capacity)) as u64;
+        let count = if finish {
+            self.padding
+                .segments(self.written + content, self.random, B)
+                - self.written
+        } else {
+            content
+        } as usize;
+
+        let len = if padded {
+            count * (B + TAGSIZE)
+        } else {
+            self.buf.len() + count * TAGSIZE
+        };
+
+        self.out.zeroize();
+        self.out.resize(len, 0u8);
+
+        let plaintext = &self.buf[..];
+        let segments = &self.segments;
+        let first = self.written;
+
+        let sealed = for_each_segment(&mut self.out, B + TAGSIZE, self.threads, |k, segment| {
+            let start = core::cmp::min(k * capacity, plaintext.len());
+            let end = core::cmp::min(start + capacity, plaintext.len());
+            let (content, tag) = segment.split_at_mut(segment.len() - TAGSIZE);
+            content[..end - start].copy_from_slice(&plaintext[start..end]);
+
+            if padded {
+                pad_segment(content, end - start);
+            }
+
+            let last = finish && k + 1 == count;
+            tag.copy_from_slice(&segments.seal_at(segment_index(first, k)?, content, last)?);
+
+            Ok(())
+        });
+
+        sealed.into_iter().collect::<Result<(), Error>>()?;
+
+        self.w.write(&self.out)?;
+        self.buf.zeroize();
+
+        #[cfg(feature = "signing")]
+        if let Some(signer) = &mut self.signer {
+            signer.input(&self.out);
+        }
+        self.written += count as u64;
+
+        Ok(())
+    }
+
+    /// Seal and write the remaining segments, the last of which marks the stream as complete,
+    /// followed by the signature of a signed stream.
+    ///
+    /// Yields the underlying writer, and the total number of bytes written to it.
+    pub fn finish(mut self) -> Result<(W, u64), Error> {
+        self.write_batch(true)?;
+
+        #[cfg(feature = "signing")]
+        if let Some(signer) = self.signer.take() {
+            self.w.write(&signer.sign())?;
+        }
+
+        Ok(self.w.disclose())
+    }
+}
+
+impl<W: Writable, const B: usize> Writable for ParallelSealer<'_, W, B> {
+    fn write(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+        let batch = self.batch_size();
+
+        while !buf.is_empty() {
+            // Only write a full batch once more plaintext follows, as it might contain the final segment.
+            if self.buf.len() == batch {
+                self.write_batch(false)?;
+            }
+
+            let n = core::cmp::min(batch - self.buf.len(), buf.len());
+            self.buf.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Writable, const B: usize> std::io::Write for ParallelSealer<'_, W, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Writable::write(self, buf)?;
+        Ok(buf.len())
+    }
+
+    /// Batches are only written once they are full, or by `finish`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Second stage opener of an IRMAseal encrypted bytestream, which opens batches of segments across several threads.
+///
+/// Every segment is authenticated before it is yielded by `read`. If a segment is not authentic,
+/// only the plaintext of the segments that precede it is yielded.
+pub struct ParallelOpener<R: Readable> {
+    segments: Segments,
+    format: BodyFormat,
+    threads: usize,
+    metadata: Option<Metadata>,
+    r: R,
+    /// Contains a batch of segments, plus a single byte of the next batch
+    /// to determine whether the current batch contains the final segment.
+    buf: Zeroizing<Vec<u8>>,
+    len: usize,
+    /// The plaintext of the last batch.
+    plaintext: Zeroizing<Vec<u8>>,
+    /// The number of segments that have been opened.
+    opened: u64,
+    finished: bool,
+    /// The error with which the last batch failed, which is thrown after its authentic plaintext is yielded.
+    failed: Option<Error>,
+    /// The number of bytes of the last batch that were consumed through `std::io::Read`.
+    offset: usize,
+}
+
+impl<R: Readable> ParallelOpener<R> {
+    /// Throws a ConstraintViolation when `threads` is zero, or the stream is compressed.
+    pub(crate) fn new(
+        segments: Segments,
+        format: BodyFormat,
+        metadata: Option<Metadata>,
+        threads: usize,
+        r: R,
+    ) -> Result<ParallelOpener<R>, Error> {
+        if threads == 0 || format.compression != Compression::None {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let segment_size = format.segment_size();
+
+        Ok(ParallelOpener {
+            segments,
+            format,
+            threads,
+            metadata,
+            r,
+            buf: Zeroizing::new(vec![
+                0u8;
+                threads
+                    * segments_per_thread(segment_size)
+                    * segment_size
+                    + 1
+            ]),
+            len: 0,
+            plaintext: Zeroizing::new(Vec::new()),
+            opened: 0,
+            finished: false,
+            failed: None,
+            offset: 0,
+        })
+    }
+
+    /// The metadata that was sealed along with the content, if any.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Read and open the next batch of segments, and yield their content.
+    ///
+    /// Yields an empty slice for batches that only consist of padding.
+    /// Throws an IntegrityViolation if a segment is not authentic, or the stream has been truncated.
+    pub fn read(&mut self) -> Result<&[u8], Error> {
+        if self.finished {
+            return Err(Error::EndOfStream);
+        }
+
+        if let Some(e) = self.failed.take() {
+            self.failed = Some(Error::IntegrityViolation);
+            return Err(e);
+        }
+
+        let batch = self.buf.len() - 1;
+        self.len += crate::stream::util::read_fill(&mut self.r, &mut self.buf[self.len..])?;
+
+        let last = self.len <= batch;
+        let len = if last { self.len } else { batch };
+
+        let segment_size = self.format.segment_size();
+        let count = len.div_ceil(segment_size);
+        if count == 0 || len - (count - 1) * segment_size < TAGSIZE {
+            return Err(Error::PrematureEndError);
+        }
+        let segments = &self.segments;
+        let format = &self.format;
+        let first = self.opened;
+
+        let opened = for_each_segment(
+            &mut self.buf[..len],
+            segment_size,
+            self.threads,
+            |k, segment| {
+                let last = last && k + 1 == count;
+                let (content, tag) = segment.split_at_mut(segment.len() - TAGSIZE);
+                segments.open_at(segment_index(first, k)?, content, tag, last)?;
+                content_len(content, format)
+            },
+        );
+
+        self.plaintext.zeroize();
+        self.offset = 0;
+
+        for (k, opened) in opened.into_iter().enumerate() {
+            match opened {
+                Ok(n) => {
+                    let start = k * segment_size;
+                    self.plaintext
+                        .extend_from_slice(&self.buf[start..start + n]);
+                }
+                Err(e) => {
+                    self.failed = Some(e);
+                    break;
+                }
+            }
+        }
+
+        if self.failed.is_some() && self.plaintext.is_empty() {
+            return self.read();
+        }
+
+        if self.failed.is_none() {
+            self.opened += count as u64;
+            self.finished = last;
+        }
+
+        // Retain the single byte of the next batch.
+        if !last {
+            self.buf[0] = self.buf[batch];
+            self.len = 1;
+        }
+
+        Ok(&self.plaintext)
+    }
+
+    /// Will check whether the entire stream was authentic once it is exhausted.
+    /// Will only yield the correct value once the **entire** stream is read
+    /// using `write_to`, or by manually calling `read` until `Error::EndOfStream` is yielded.
+    pub fn validate(self) -> bool {
+        self.is_valid()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.finished
+    }
+
+    /// Will block and write the entire stream to the argument writer.
+    pub fn write_to<W: Writable>(&mut self, w: &mut W) -> Result<(), Error> {
+        loop {
+            match self.read() {
+                Ok(buf) => w.write(buf)?,
+                Err(Error::EndOfStream) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+        }
+    }
+}
+
+/// Yields the plaintext, and fails with `ErrorKind::InvalidData` once it turns out not to be authentic.
+impl<R: Readable> std::io::Read for ParallelOpener<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.offset < self.plaintext.len() {
+                let n = core::cmp::min(buf.len(), self.plaintext.len() - self.offset);
+                buf[..n].copy_from_slice(&self.plaintext[self.offset..self.offset + n]);
+                self.offset += n;
+                return Ok(n);
+            }
+
+            match ParallelOpener::read(self).map(|_| ()) {
+                Ok(()) => {}
+                Err(Error::EndOfStream) if self.is_valid() => return Ok(0),
+                Err(Error::EndOfStream) => return Err(Error::IntegrityViolation.into()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Passphrase mode, in which the body key is additionally wrapped with a key that is derived
+//! from a passphrase using the memory-hard Argon2id KDF.
+//!
+//! A stream sealed in passphrase mode can also be unsealed without an IRMA app, for example
+//! by an operator that has to recover it. The salt and the cost of the derivation are recorded
+//! in the header, such that the cost can be raised without breaking existing streams.
+
+use crate::stream::*;
+use crate::*;
+
+use arrayref::array_ref;
+use rand::{CryptoRng, Rng};
+use zeroize::Zeroizing;
+
+use crate::stream::util::Key;
+
+pub(crate) const SALTSIZE: usize = 16;
+
+/// The maximum memory cost in KiB that a header can demand, which is 128 MiB.
+///
+/// Limits the memory a malicious stream can make the opener allocate.
+pub(crate) const MAX_MEMORY_COST: u32 = 1 << 17;
+
+/// The maximum number of passes that a header can demand.
+///
+/// Together with `MAX_MEMORY_COST`, limits the time a malicious stream can make the opener spend.
+pub(crate) const MAX_ITERATIONS: u32 = 16;
+
+/// The maximum number of lanes that a header can demand.
+pub(crate) const MAX_PARALLELISM: u32 = 8;
+
+/// The cost of deriving the key from a passphrase using Argon2id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassphraseCost {
+    /// The memory that is used, in KiB.
+    pub memory: u32,
+    /// The number of passes over the memory.
+    pub iterations: u32,
+    /// The number of lanes that can be computed in parallel.
+    pub parallelism: u32,
+}
+
+/// Follows the minimal configuration of Argon2id that OWASP recommends.
+impl Default for PassphraseCost {
+    fn default() -> Self {
+        PassphraseCost {
+            memory: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// A passphrase for which the body key is wrapped, along with the cost of deriving its key.
+///
+/// Is overwritten with zeroes when dropped.
+#[derive(Clone)]
+pub struct Passphrase {
+    passphrase: Zeroizing<std::string::String>,
+    cost: PassphraseCost,
+}
+
+impl core::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Passphrase")
+            .field("cost", &self.cost)
+            .finish()
+    }
+}
+
+impl Passphrase {
+    /// A passphrase of which the key is derived at the default cost.
+    pub fn new(passphrase: &str) -> Passphrase {
+        Passphrase::with_cost(passphrase, PassphraseCost::default())
+    }
+
+    pub fn with_cost(passphrase: &str, cost: PassphraseCost) -> Passphrase {
+        Passphrase {
+            passphrase: Zeroizing::new(passphrase.into()),
+            cost,
+        }
+    }
+
+    pub fn cost(&self) -> PassphraseCost {
+        self.cost
+    }
+}
+
+/// The body key as wrapped for a passphrase, as listed in the header.
+pub(crate) struct PassphraseRecipient {
+    cost: PassphraseCost,
+    salt: [u8; SALTSIZE],
+    wrapped_key: [u8; KEYSIZE],
+}
+
+/// The Argon2 parameters for the cost, unless Argon2 does not accept it or it exceeds
+/// `MAX_MEMORY_COST`, `MAX_ITERATIONS` or `MAX_PARALLELISM`.
+fn params(cost: PassphraseCost) -> Option<argon2::Params> {
+    if cost.memory > MAX_MEMORY_COST
+        || cost.iterations > MAX_ITERATIONS
+        || cost.parallelism > MAX_PARALLELISM
+    {
+        return None;
+    }
+
+    argon2::Params::new(
+        cost.memory,
+        cost.iterations,
+        cost.parallelism,
+        Some(KEYSIZE),
+    )
+    .ok()
+}
+
+/// Derive the key with which the body key is wrapped.
+///
+/// Throws a ConstraintViolation when the cost is not accepted.
+fn derive_key(passphrase: &str, salt: &[u8], cost: PassphraseCost) -> Result<Key, Error> {
+    let params = params(cost).ok_or(Error::ConstraintViolation)?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+    let mut key = Key::new([0u8; KEYSIZE]);
+    argon2
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key[..])
+        .or(Err(Error::ConstraintViolation))?;
+
+    Ok(key)
+}
+
+impl PassphraseRecipient {
+    /// Wrap the body key for the passphrase, using a fresh salt.
+    pub fn seal<R: Rng + CryptoRng>(
+        passphrase: &Passphrase,
+        key: &[u8; KEYSIZE],
+        rng: &mut R,
+    ) -> Result<PassphraseRecipient, Error> {
+        let mut salt = [0u8; SALTSIZE];
+        rng.fill_bytes(&mut salt);
+
+        let derived = derive_key(&passphrase.passphrase, &salt, passphrase.cost)?;
+
+        Ok(PassphraseRecipient {
+            cost: passphrase.cost,
+            salt,
+            wrapped_key: *crate::stream::util::mask_key(&derived[..], key),
+        })
+    }
+
+    /// Unwrap the body key. A wrong passphrase yields another key, which the caller has to detect.
+    pub fn unwrap(&self, passphrase: &str) -> Result<Key, Error> {
+        let derived = derive_key(passphrase, &self.salt, self.cost)?;
+        Ok(crate::stream::util::mask_key(
+            &derived[..],
+            &self.wrapped_key,
+        ))
+    }
+
+    /// Throws a FormatViolation when the cost is not accepted by Argon2, or exceeds one of the maximums.
+    pub fn read_from<R: Readable>(mut r: R) -> Result<PassphraseRecipient, Error> {
+        let mut cost = [0u32; 3];
+        for c in cost.iter_mut() {
+            *c = u32::from_be_bytes(*array_ref![r.read_bytes_strict(4)?, 0, 4]);
+        }
+
+        let cost = PassphraseCost {
+            memory: cost[0],
+            iterations: cost[1],
+            parallelism: cost[2],
+        };
+        if params(cost).is_none() {
+            return Err(Error::FormatViolation);
+        }
+
+        let salt = *array_ref![r.read_bytes_strict(SALTSIZE)?, 0, SALTSIZE];
+        let wrapped_key = *array_ref![r.read_bytes_strict(KEYSIZE)?, 0, KEYSIZE];
+
+        Ok(PassphraseRecipient {
+            cost,
+            salt,
+            wrapped_key,
+        })
+    }
+
+    pub fn write_to<W: Writable>(&self, mut w: W) -> Result<(), Error> {
+        w.write(&self.cost.memory.to_be_bytes())?;
+        w.write(&self.cost.iterations.to_be_bytes())?;
+        w.write(&self.cost.parallelism.to_be_bytes())?;
+        w.write(&self.salt)?;
+        w.write(&self.wrapped_key)
+    }
+}
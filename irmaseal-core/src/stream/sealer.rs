@@ -1,50 +1,408 @@
-use ctr::stream_cipher::{NewStreamCipher, StreamCipher};
+#[cfg(not(feature = "signing"))]
+use core::marker::PhantomData;
 use hmac::Mac;
 use rand::{CryptoRng, Rng};
+use zeroize::Zeroizing;
 
+use crate::kem::Kem;
+#[cfg(feature = "compression")]
+use crate::stream::compression::Compressor;
+use crate::stream::header::Header;
+use crate::stream::segment::Segments;
+#[cfg(feature = "signing")]
+use crate::stream::sender::BodySigner;
+use crate::stream::util::CountingWriter;
 use crate::stream::*;
 use crate::*;
 
+/// Policy to hide the length of the content, by padding the body with additional segments.
+///
+/// Padded streams carry the length of the content of every segment in a trailer,
+/// such that all segments, including the final one, are of equal size.
+/// The padding is authenticated along with the content, and stripped by the `OpenerUnsealed`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Padding {
+    /// Do not pad the body.
+    #[default]
+    None,
+    /// Pad the body to a power of two number of segments.
+    PowerOfTwo,
+    /// Pad the body to a multiple of the bucket size in bytes, rounded up to whole segments.
+    Bucket(u64),
+    /// Pad the body with a uniformly random number of bytes up to the maximum, rounded up to whole segments.
+    Random(u64),
+}
+
+/// Pad the content of a segment of `len` bytes to the full block, ending with a trailer that holds its length.
+pub(crate) fn pad_segment(block: &mut [u8], len: usize) {
+    let capacity = block.len() - PADDING_TRAILERSIZE;
+    for b in block[len..capacity].iter_mut() {
+        *b = 0;
+    }
+
+    // Fits because the block size is valid.
+    block[capacity..].copy_from_slice(&(len as u32).to_be_bytes());
+}
+
+/// The number of segments that are required for the number of bytes, rounded up.
+fn segments_for(size: u64, block_size: usize) -> u64 {
+    size.div_ceil((block_size + TAGSIZE) as u64)
+}
+
+impl Padding {
+    /// Draw the number of random padding segments, which is only non-zero for random padding.
+    pub(crate) fn random<R: Rng + CryptoRng>(&self, rng: &mut R, block_size: usize) -> u64 {
+        match *self {
+            Padding::Random(max) => rng.gen_range(0, segments_for(max, block_size) + 1),
+            _ => 0,
+        }
+    }
+
+    /// The size of the content of a single segment.
+    pub(crate) fn capacity(&self, block_size: usize) -> usize {
+        match self {
+            Padding::None => block_size,
+            _ => block_size - PADDING_TRAILERSIZE,
+        }
+    }
+
+    /// The number of segments a body of `n` segments is padded to.
+    pub(crate) fn segments(&self, n: u64, random: u64, block_size: usize) -> u64 {
+        match *self {
+            Padding::None => n,
+            Padding::PowerOfTwo => n.next_power_of_two(),
+            Padding::Bucket(size) => {
+                let bucket = core::cmp::max(1, segments_for(size, block_size));
+                n.div_ceil(bucket) * bucket
+            }
+            Padding::Random(_) => n + random,
+        }
+    }
+}
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const TIMESTAMP_POLICY_DAY: u8 = 0x01;
+const TIMESTAMP_POLICY_WEEK: u8 = 0x02;
+
+/// Policy to round the timestamps of the recipients down to the start of an epoch.
+///
+/// The private key of an identity is bound to its timestamp. With rounded timestamps, a recipient
+/// can unseal every stream that is sealed for it within the same epoch using the same key.
+/// This trades the freshness of the disclosure for the number of disclosures the recipient has to do.
+///
+/// Rounding down releases the key of a stream that is sealed for a future timestamp early.
+/// Round such a timestamp up using `round_up` before sealing instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPolicy {
+    /// Use the timestamps of the recipients as given.
+    #[default]
+    Exact,
+    /// Round the timestamps down to the start of the day, at midnight UTC.
+    Day,
+    /// Round the timestamps down to the start of the week, at midnight UTC on Monday.
+    Week,
+}
+
+impl TimestampPolicy {
+    /// Round the timestamp down to the start of its epoch, as the sealer does.
+    ///
+    /// Yields nothing when the start of the epoch can not be represented.
+    pub fn round_down(self, timestamp: u64) -> Option<u64> {
+        match self {
+            TimestampPolicy::Exact => Some(timestamp),
+            TimestampPolicy::Day => Some(timestamp - timestamp % SECS_PER_DAY),
+            // The UNIX epoch lies on a Thursday, three days after the start of its week.
+            TimestampPolicy::Week => {
+                let shifted = timestamp.checked_add(3 * SECS_PER_DAY)?;
+                Some((shifted - shifted % (7 * SECS_PER_DAY)).saturating_sub(3 * SECS_PER_DAY))
+            }
+        }
+    }
+
+    /// Round the timestamp up to the start of the next epoch, unless it starts an epoch already.
+    ///
+    /// Yields nothing when the start of the epoch can not be represented.
+    pub fn round_up(self, timestamp: u64) -> Option<u64> {
+        let down = self.round_down(timestamp)?;
+        match self {
+            TimestampPolicy::Exact => Some(timestamp),
+            _ if down == timestamp => Some(timestamp),
+            TimestampPolicy::Day => down.checked_add(SECS_PER_DAY),
+            TimestampPolicy::Week if timestamp < 4 * SECS_PER_DAY => Some(4 * SECS_PER_DAY),
+            TimestampPolicy::Week => down.checked_add(7 * SECS_PER_DAY),
+        }
+    }
+
+    /// The byte representation of the policy, as written to the header.
+    pub(crate) fn to_byte(self) -> Option<u8> {
+        match self {
+            TimestampPolicy::Exact => None,
+            TimestampPolicy::Day => Some(TIMESTAMP_POLICY_DAY),
+            TimestampPolicy::Week => Some(TIMESTAMP_POLICY_WEEK),
+        }
+    }
+
+    /// Throws a FormatViolation for policies that are not known.
+    pub(crate) fn from_byte(b: u8) -> Result<TimestampPolicy, Error> {
+        match b {
+            TIMESTAMP_POLICY_DAY => Ok(TimestampPolicy::Day),
+            TIMESTAMP_POLICY_WEEK => Ok(TimestampPolicy::Week),
+            _ => Err(Error::FormatViolation),
+        }
+    }
+}
+
+/// Optional properties of an IRMAseal encrypted bytestream.
+///
+/// Is borrowed by the sealer until it is finished, as it signs the stream using the key of the `sender`.
+#[derive(Debug, Default)]
+pub struct SealOptions {
+    /// Is sealed along with the content, and can be read by the recipient before the body.
+    pub metadata: Option<Metadata>,
+    /// Only expose the attribute type of every recipient, and a short hint of the attribute value.
+    ///
+    /// The recipient has to provide its full identity when unsealing.
+    pub hide_values: bool,
+    /// Hides the length of the content.
+    pub padding: Padding,
+    /// Compresses the content before it is sealed.
+    ///
+    /// **Warning**: compression reveals information about the content through its length.
+    /// Leave it disabled for sensitive content.
+    pub compression: Compression,
+    /// Rounds the timestamps of the recipients down to the start of an epoch, such that a single
+    /// key unseals all streams that are sealed for the recipient within that epoch.
+    ///
+    /// Throws a ConstraintViolation when the start of the epoch of a timestamp can not be represented.
+    pub timestamp_policy: TimestampPolicy,
+    /// Also encapsulates the body key for the post-quantum key of every recipient, in the same order.
+    ///
+    /// Throws a ConstraintViolation when the number of keys does not match the number of recipients.
+    #[cfg(feature = "hybrid")]
+    pub hybrid: Option<std::vec::Vec<HybridPublicKey>>,
+    /// Signs the header and the sealed body, such that the recipients can verify who sealed the stream.
+    ///
+    /// The signature trails the body, hence signed streams can not be opened in parallel or seekably.
+    #[cfg(feature = "signing")]
+    pub sender: Option<SenderKey>,
+    /// Also wraps the body key for a passphrase, such that the stream can be unsealed
+    /// using `unseal_with_passphrase` as well as by its recipients.
+    #[cfg(feature = "passphrase")]
+    pub passphrase: Option<Passphrase>,
+    /// Also wraps the body key for the escrow key of the organisation, such that it can
+    /// recover the stream using `unseal_with_escrow`.
+    #[cfg(feature = "escrow")]
+    pub escrow: Option<EscrowPublicKey>,
+}
+
+/// The default options, which outlive every sealer.
+pub(crate) static DEFAULT_OPTIONS: SealOptions = SealOptions {
+    metadata: None,
+    hide_values: false,
+    padding: Padding::None,
+    compression: Compression::None,
+    timestamp_policy: TimestampPolicy::Exact,
+    #[cfg(feature = "hybrid")]
+    hybrid: None,
+    #[cfg(feature = "signing")]
+    sender: None,
+    #[cfg(feature = "passphrase")]
+    passphrase: None,
+    #[cfg(feature = "escrow")]
+    escrow: None,
+};
+
 /// Sealer for an bytestream, which converts it into an IRMAseal encrypted bytestream.
-pub struct Sealer<'a, W: Writable> {
-    aes: SymCrypt,
-    hmac: Verifier,
-    w: &'a mut W,
+///
+/// The plaintext is buffered until a full segment of `B` bytes is available, which is then sealed and written.
+/// The final segment is only written by `finish`. A `Sealer` that is dropped without finishing
+/// yields a truncated stream, which will be rejected by the `OpenerUnsealed`.
+///
+/// A block size other than `BLOCKSIZE` is recorded in the header, and is picked up by the openers.
+/// Without `std`, the `OpenerSealed` needs a buffer of at least the block size.
+/// It can be chosen using `with_block_size`.
+pub struct Sealer<'a, W: Writable, const B: usize = BLOCKSIZE> {
+    segments: Segments,
+    buf: Zeroizing<[u8; B]>,
+    len: usize,
+    /// The number of segments that have been written.
+    written: u64,
+    padding: Padding,
+    /// The number of random padding segments, determined in advance as `finish` has no access to an rng.
+    random: u64,
+    #[cfg(feature = "compression")]
+    compressor: Option<Compressor>,
+    #[cfg(feature = "signing")]
+    signer: Option<BodySigner<'a>>,
+    /// Without signed mode, no key of the options is borrowed.
+    #[cfg(not(feature = "signing"))]
+    signer: PhantomData<&'a SealOptions>,
+    w: CountingWriter<W>,
 }
 
 impl<'a, W: Writable> Sealer<'a, W> {
-    pub fn new<R: Rng + CryptoRng>(
+    /// Seal a bytestream for a single recipient.
+    pub fn new<K: Kem, R: Rng + CryptoRng>(
         i: &Identity,
-        pk: &PublicKey,
+        pk: &PublicKey<K>,
         rng: &mut R,
-        w: &'a mut W,
+        w: W,
     ) -> Result<Sealer<'a, W>, Error> {
-        let (c, k) = ibe::kiltz_vahlis_one::encrypt(&pk.0, &i.derive(), rng);
+        Self::with_recipients(core::slice::from_ref(i), pk, rng, w)
+    }
 
-        let (aeskey, mackey) = crate::stream::util::derive_keys(&k);
-        let iv = crate::stream::util::generate_iv(rng);
+    /// Seal a bytestream for several recipients at once.
+    ///
+    /// A single body key is generated, which is wrapped for every recipient separately.
+    /// Throws a ConstraintViolation when there are no recipients, or more than `MAX_RECIPIENTS`.
+    pub fn with_recipients<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<Sealer<'a, W>, Error> {
+        Self::with_options(recipients, &DEFAULT_OPTIONS, pk, rng, w)
+    }
 
-        let aes = SymCrypt::new(&aeskey.into(), &iv.into());
-        let mut hmac = Verifier::new_varkey(&mackey).unwrap();
+    /// Seal a bytestream for several recipients at once, with optional properties such as metadata.
+    pub fn with_options<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        options: &'a SealOptions,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<Sealer<'a, W>, Error> {
+        Self::with_block_size(recipients, options, pk, rng, w)
+    }
+}
 
-        let ciphertext = c.to_bytes();
+impl<'a, W: Writable, const B: usize> Sealer<'a, W, B> {
+    /// Seal a bytestream for several recipients at once, in segments of `B` bytes of plaintext.
+    ///
+    /// For example `Sealer::<_, 65536>::with_block_size(..)` trades memory for throughput.
+    /// Throws a ConstraintViolation when `B` is too small to hold a padding trailer, or exceeds `MAX_BLOCKSIZE`.
+    pub fn with_block_size<K: Kem, R: Rng + CryptoRng>(
+        recipients: &[Identity],
+        options: &'a SealOptions,
+        pk: &PublicKey<K>,
+        rng: &mut R,
+        w: W,
+    ) -> Result<Sealer<'a, W, B>, Error> {
+        let mut w = CountingWriter::new(w);
+        let segments = Header::write_to(recipients, options, B, pk, rng, &mut w)?;
+        let random = options.padding.random(rng, B);
+        #[cfg(feature = "signing")]
+        let signer = options
+            .sender
+            .as_ref()
+            .map(|key| BodySigner::new(key, segments.aad()));
+        #[cfg(not(feature = "signing"))]
+        let signer = PhantomData;
 
-        hmac.input(&PRELUDE);
-        w.write(&PRELUDE)?;
+        Ok(Sealer {
+            segments,
+            buf: Zeroizing::new([0u8; B]),
+            len: 0,
+            written: 0,
+            padding: options.padding,
+            random,
+            #[cfg(feature = "compression")]
+            compressor: Compressor::new(options.compression),
+            signer,
+            w,
+        })
+    }
 
-        hmac.input(&[FORMAT_VERSION]);
-        w.write(&[FORMAT_VERSION])?;
+    /// The size of the content of a single segment.
+    fn capacity(&self) -> usize {
+        self.padding.capacity(B)
+    }
 
-        i.write_to(&mut hmac)?;
-        i.write_to(w)?;
+    /// The writer to which the sealed stream is written.
+    #[cfg(feature = "async")]
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        self.w.get_mut()
+    }
+
+    /// Seal the buffered plaintext as a single segment, and write it.
+    fn write_segment(&mut self, last: bool) -> Result<(), Error> {
+        let len = match self.padding {
+            Padding::None => self.len,
+            _ => {
+                pad_segment(&mut self.buf[..], self.len);
+                B
+            }
+        };
 
-        hmac.input(&ciphertext);
-        w.write(&ciphertext)?;
+        let tag = self.segments.seal(&mut self.buf[..len], last)?;
+        self.w.write(&self.buf[..len])?;
+        self.w.write(&tag)?;
 
-        hmac.input(&iv);
-        w.write(&iv)?;
+        #[cfg(feature = "signing")]
+        if let Some(signer) = &mut self.signer {
+            signer.input(&self.buf[..len]);
+            signer.input(&tag);
+        }
+        self.len = 0;
+        self.written += 1;
 
-        Ok(Sealer { aes, hmac, w })
+        Ok(())
+    }
+
+    /// Compress the plaintext into the buffer, sealing every segment that is filled.
+    ///
+    /// If `finish` is set, the compressed stream is completed, which leaves the final segment buffered.
+    #[cfg(feature = "compression")]
+    fn compress(&mut self, mut buf: &[u8], finish: bool) -> Result<(), Error> {
+        while finish || !buf.is_empty() {
+            if self.len == self.capacity() {
+                self.write_segment(false)?;
+            }
+
+            let capacity = self.capacity();
+            let (consumed, written, finished) = match &mut self.compressor {
+                Some(c) => c.compress(buf, &mut self.buf[self.len..capacity], finish)?,
+                None => return Err(Error::ConstraintViolation),
+            };
+
+            self.len += written;
+            buf = &buf[consumed..];
+
+            if finished {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Seal and write the final segment, which marks the stream as complete,
+    /// followed by the signature of a signed stream.
+    ///
+    /// Yields the underlying writer, and the total number of bytes written to it.
+    pub fn finish(mut self) -> Result<(W, u64), Error> {
+        #[cfg(feature = "compression")]
+        {
+            if self.compressor.is_some() {
+                self.compress(&[], true)?;
+            }
+        }
+
+        let target = self.padding.segments(self.written + 1, self.random, B);
+
+        loop {
+            let last = self.written + 1 >= target;
+            self.write_segment(last)?;
+
+            if last {
+                #[cfg(feature = "signing")]
+                if let Some(signer) = self.signer.take() {
+                    self.w.write(&signer.sign())?;
+                }
+
+                return Ok(self.w.disclose());
+            }
+        }
     }
 }
 
@@ -55,25 +413,40 @@ impl Writable for Verifier {
     }
 }
 
-impl<'a, W: Writable> Writable for Sealer<'a, W> {
-    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
-        let mut tmp = [0u8; BLOCKSIZE];
-
-        for c in buf.chunks(BLOCKSIZE) {
-            let subtmp = &mut tmp[0..c.len()];
-            subtmp.copy_from_slice(c);
-            self.aes.encrypt(subtmp);
-            self.hmac.input(subtmp);
-            self.w.write(subtmp)?;
+impl<W: Writable, const B: usize> Writable for Sealer<'_, W, B> {
+    fn write(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "compression")]
+        {
+            if self.compressor.is_some() {
+                return self.compress(buf, false);
+            }
+        }
+
+        while !buf.is_empty() {
+            // Only write a full segment once more plaintext follows, as it might be the final one.
+            if self.len == self.capacity() {
+                self.write_segment(false)?;
+            }
+
+            let n = core::cmp::min(self.capacity() - self.len, buf.len());
+            self.buf[self.len..self.len + n].copy_from_slice(&buf[..n]);
+            self.len += n;
+            buf = &buf[n..];
         }
 
         Ok(())
     }
 }
 
-impl<'a, W: Writable> Drop for Sealer<'a, W> {
-    fn drop(&mut self) {
-        let code = self.hmac.result_reset().code();
-        self.w.write(&code).unwrap()
+#[cfg(feature = "std")]
+impl<W: Writable, const B: usize> std::io::Write for Sealer<'_, W, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Writable::write(self, buf)?;
+        Ok(buf.len())
+    }
+
+    /// Segments are only written once they are full, or by `finish`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
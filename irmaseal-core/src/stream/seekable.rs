@@ -0,0 +1,178 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::vec::Vec;
+
+use crate::stream::header::BodyFormat;
+use crate::stream::segment::Segments;
+use crate::stream::*;
+use crate::*;
+
+use zeroize::Zeroizing;
+
+/// Second stage opener of an IRMAseal encrypted bytestream, which supports random access to the plaintext.
+///
+/// Only the segments that cover the requested range are read, each of which is authenticated
+/// before it is released. As the final segment is sealed as such, truncating the stream
+/// on a segment boundary is detected when reading the end of the plaintext.
+///
+/// Is only supported for format version 2 streams that are neither padded nor compressed,
+/// as the position of the plaintext in the body can otherwise not be determined in advance.
+pub struct SeekableOpener<R: Read + Seek> {
+    segments: Segments,
+    metadata: Option<Metadata>,
+    r: R,
+    /// The size of the plaintext of every segment.
+    block_size: usize,
+    /// The position of the first segment in the underlying reader.
+    start: u64,
+    /// The number of segments in the body.
+    count: u64,
+    /// The size of the final segment, including its authentication tag.
+    lastlen: usize,
+    /// The length of the plaintext.
+    len: u64,
+    /// The position in the plaintext.
+    pos: u64,
+    buf: Zeroizing<Vec<u8>>,
+    /// The index of the segment that is opened in `buf`.
+    current: Option<u64>,
+}
+
+impl<R: Read + Seek> SeekableOpener<R> {
+    /// Determine the layout of the body, which starts at `start`.
+    ///
+    /// Throws a ConstraintViolation if the stream is padded or compressed.
+    pub(crate) fn new(
+        segments: Segments,
+        format: BodyFormat,
+        metadata: Option<Metadata>,
+        mut r: R,
+        start: u64,
+    ) -> Result<SeekableOpener<R>, Error> {
+        if format.padded || format.compression != Compression::None {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let end = r.seek(SeekFrom::End(0))?;
+        let bodylen = end.checked_sub(start).ok_or(Error::PrematureEndError)?;
+
+        let segment_size = format.segment_size() as u64;
+        let count = core::cmp::max(1, bodylen.div_ceil(segment_size));
+        let lastlen = (bodylen - (count - 1) * segment_size) as usize;
+        if lastlen < TAGSIZE {
+            return Err(Error::PrematureEndError);
+        }
+
+        // The index of every segment is part of its nonce.
+        if count - 1 > u64::from(u32::MAX) {
+            return Err(Error::FormatViolation);
+        }
+
+        Ok(SeekableOpener {
+            segments,
+            metadata,
+            r,
+            block_size: format.block_size,
+            start,
+            count,
+            lastlen,
+            len: bodylen - count * TAGSIZE as u64,
+            pos: 0,
+            buf: Zeroizing::new(vec![0u8; format.segment_size()]),
+            current: None,
+        })
+    }
+
+    /// The metadata that was sealed along with the content, if any.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The length of the plaintext in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the plaintext is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Read and open the segment at `index`, unless it is opened already.
+    ///
+    /// Throws an IntegrityViolation if the segment is not authentic.
+    fn open_segment(&mut self, index: u64) -> Result<(), Error> {
+        if self.current == Some(index) {
+            return Ok(());
+        }
+
+        self.current = None;
+
+        let last = index + 1 == self.count;
+        let segment_size = self.block_size + TAGSIZE;
+        let segmentlen = if last { self.lastlen } else { segment_size };
+
+        self.r
+            .seek(SeekFrom::Start(self.start + index * segment_size as u64))?;
+        self.r.read_exact(&mut self.buf[..segmentlen])?;
+
+        let (content, tag) = self.buf[..segmentlen].split_at_mut(segmentlen - TAGSIZE);
+        self.segments.open_at(index as u32, content, tag, last)?;
+        self.current = Some(index);
+
+        Ok(())
+    }
+}
+
+/// Yields the plaintext from the current position, and fails with `ErrorKind::InvalidData`
+/// if a segment that covers it, or the final segment when at the end, is not authentic.
+impl<R: Read + Seek> Read for SeekableOpener<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // The end of the plaintext is only reported once the final segment turns out to be authentic.
+        if self.pos >= self.len {
+            self.open_segment(self.count - 1)?;
+            return Ok(0);
+        }
+
+        let index = self.pos / self.block_size as u64;
+        let offset = (self.pos % self.block_size as u64) as usize;
+        self.open_segment(index)?;
+
+        let content = if index + 1 == self.count {
+            self.lastlen - TAGSIZE
+        } else {
+            self.block_size
+        };
+
+        let n = core::cmp::min(buf.len(), content - offset);
+        buf[..n].copy_from_slice(&self.buf[offset..offset + n]);
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+/// Seeks in the plaintext. Seeking beyond the end is allowed, after which nothing is read.
+impl<R: Read + Seek> Seek for SeekableOpener<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{AeadInPlace, NewAead};
+
+use crate::stream::*;
+use crate::*;
+
+/// Segmented authenticated encryption of a bytestream, following the STREAM construction.
+///
+/// Every segment is sealed separately with a nonce consisting of a random prefix,
+/// the index of the segment and a flag that marks the final segment.
+/// Hence every segment can be authenticated before it is released,
+/// and reordering or truncating segments is detected.
+pub(crate) struct Segments {
+    aead: SegmentCipher,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+    counter: u32,
+    aad: [u8; DIGESTSIZE],
+    exhausted: bool,
+}
+
+impl Segments {
+    /// The associated data is authenticated along with every segment.
+    pub fn new(
+        key: &[u8; KEYSIZE],
+        nonce_prefix: &[u8; NONCE_PREFIX_SIZE],
+        aad: &[u8; DIGESTSIZE],
+    ) -> Segments {
+        Segments {
+            aead: SegmentCipher::new(GenericArray::from_slice(key)),
+            nonce_prefix: *nonce_prefix,
+            counter: 0,
+            aad: *aad,
+            exhausted: false,
+        }
+    }
+
+    /// The associated data, which is the digest of the header.
+    #[cfg_attr(not(feature = "signing"), allow(dead_code))]
+    pub fn aad(&self) -> &[u8; DIGESTSIZE] {
+        &self.aad
+    }
+
+    /// The nonce of the segment at `index`.
+    fn nonce(&self, index: u32, last: bool) -> [u8; NONCESIZE] {
+        let mut nonce = [0u8; NONCESIZE];
+        nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_SIZE..NONCESIZE - 1].copy_from_slice(&index.to_be_bytes());
+        nonce[NONCESIZE - 1] = last as u8;
+        nonce
+    }
+
+    fn next_nonce(&mut self, last: bool) -> Result<[u8; NONCESIZE], Error> {
+        if self.exhausted {
+            return Err(Error::ConstraintViolation);
+        }
+
+        let nonce = self.nonce(self.counter, last);
+
+        match self.counter.checked_add(1) {
+            Some(counter) => self.counter = counter,
+            None => self.exhausted = true,
+        }
+
+        Ok(nonce)
+    }
+
+    /// Encrypt the next segment in place, yielding its authentication tag.
+    pub fn seal(&mut self, buf: &mut [u8], last: bool) -> Result<[u8; TAGSIZE], Error> {
+        let nonce = self.next_nonce(last)?;
+        self.seal_with(&nonce, buf)
+    }
+
+    /// Encrypt the segment at `index` in place, yielding its authentication tag.
+    ///
+    /// Does not affect the position of `seal`, such that segments can be sealed in any order.
+    #[cfg(feature = "std")]
+    pub fn seal_at(&self, index: u32, buf: &mut [u8], last: bool) -> Result<[u8; TAGSIZE], Error> {
+        self.seal_with(&self.nonce(index, last), buf)
+    }
+
+    fn seal_with(&self, nonce: &[u8; NONCESIZE], buf: &mut [u8]) -> Result<[u8; TAGSIZE], Error> {
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(GenericArray::from_slice(nonce), &self.aad, buf)
+            .or(Err(Error::ConstraintViolation))?;
+
+        let mut res = [0u8; TAGSIZE];
+        res.copy_from_slice(&tag);
+        Ok(res)
+    }
+
+    /// Decrypt the next segment in place, only if it is authentic.
+    ///
+    /// Throws an IntegrityViolation if the segment or its position in the stream has been tampered with.
+    pub fn open(&mut self, buf: &mut [u8], tag: &[u8], last: bool) -> Result<(), Error> {
+        let nonce = self.next_nonce(last)?;
+        self.open_with(&nonce, buf, tag)
+    }
+
+    /// Decrypt the segment at `index` in place, only if it is authentic.
+    ///
+    /// Does not affect the position of `open`, such that segments can be opened in any order.
+    #[cfg(feature = "std")]
+    pub fn open_at(&self, index: u32, buf: &mut [u8], tag: &[u8], last: bool) -> Result<(), Error> {
+        self.open_with(&self.nonce(index, last), buf, tag)
+    }
+
+    fn open_with(&self, nonce: &[u8; NONCESIZE], buf: &mut [u8], tag: &[u8]) -> Result<(), Error> {
+        self.aead
+            .decrypt_in_place_detached(
+                GenericArray::from_slice(nonce),
+                &self.aad,
+                buf,
+                GenericArray::from_slice(tag),
+            )
+            .or(Err(Error::IntegrityViolation))
+    }
+}
+
+/// Encrypt a single message in place, with a key that is used for this message only.
+pub(crate) fn seal_single(key: &[u8; KEYSIZE], buf: &mut [u8]) -> Result<[u8; TAGSIZE], Error> {
+    let tag = SegmentCipher::new(GenericArray::from_slice(key))
+        .encrypt_in_place_detached(&GenericArray::default(), b"", buf)
+        .or(Err(Error::ConstraintViolation))?;
+
+    let mut res = [0u8; TAGSIZE];
+    res.copy_from_slice(&tag);
+    Ok(res)
+}
+
+/// Decrypt a single message in place that was encrypted by `seal_single`, only if it is authentic.
+pub(crate) fn open_single(key: &[u8; KEYSIZE], buf: &mut [u8], tag: &[u8]) -> Result<(), Error> {
+    SegmentCipher::new(GenericArray::from_slice(key))
+        .decrypt_in_place_detached(
+            &GenericArray::default(),
+            b"",
+            buf,
+            GenericArray::from_slice(tag),
+        )
+        .or(Err(Error::IntegrityViolation))
+}
@@ -0,0 +1,385 @@
+//! Signed mode, in which the sender of a stream is authenticated by means of an identity-based
+//! signature that is issued via the PKG.
+//!
+//! Much like it extracts user secret keys, the PKG issues an Ed25519 signing key for the identity
+//! of a sender, along with a certificate that binds its verifying key to that identity.
+//! The sender lists the certificate in the header, and signs the digest of the header and the
+//! sealed body. The signature trails the final segment, and is verified once the body is read.
+
+use crate::stream::util::{DigestWriter, Sink};
+use crate::stream::*;
+use crate::*;
+
+use arrayref::array_ref;
+use arrayvec::ArrayVec;
+use digest::Digest;
+use ed25519_dalek::Signer as _;
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::{Zeroize, Zeroizing};
+
+/// The size of the signature that trails the body of a signed stream.
+pub const SIGNATURE_SIZE: usize = ed25519_dalek::SIGNATURE_LENGTH;
+
+const SIGNING_KEY_SIZE: usize = ed25519_dalek::SECRET_KEY_LENGTH;
+const VERIFYING_KEY_SIZE: usize = ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+/// Separates the digests that are signed by the PKG from those that are signed by senders.
+const CERTIFICATE_DOMAIN: &[u8] = b"irmaseal sender certificate";
+const TRANSCRIPT_DOMAIN: &[u8] = b"irmaseal sender signature";
+
+/// The key with which the PKG certifies the signing keys that it issues to senders.
+///
+/// Can not be cloned, and is overwritten with zeroes when dropped.
+pub struct MasterSigningKey(SigningKey);
+
+/// The public key of the PKG, with which the certificates of senders are verified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterVerifyingKey(VerifyingKey);
+
+/// A certificate of the PKG, which binds a verifying key to the identity of a sender.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenderCertificate {
+    identity: Identity,
+    key: VerifyingKey,
+    signature: Signature,
+}
+
+/// The signing key of a sender, along with the certificate of the PKG for its identity.
+///
+/// Can not be cloned, and is overwritten with zeroes when dropped.
+pub struct SenderKey {
+    key: SigningKey,
+    certificate: SenderCertificate,
+}
+
+/// Generate a signing key from the rng, of which the seed is overwritten with zeroes.
+fn generate_signing_key<R: Rng + CryptoRng>(rng: &mut R) -> SigningKey {
+    let mut seed = Zeroizing::new([0u8; SIGNING_KEY_SIZE]);
+    rng.fill_bytes(&mut seed[..]);
+    SigningKey::from_bytes(&seed)
+}
+
+/// The digest that the PKG signs to certify the verifying key for the identity.
+fn certificate_digest(identity: &Identity, key: &VerifyingKey) -> Result<[u8; DIGESTSIZE], Error> {
+    let mut dw = DigestWriter::new(HeaderDigest::new(), Sink);
+    dw.write(CERTIFICATE_DOMAIN)?;
+    identity.write_to(&mut dw)?;
+    dw.write(key.as_bytes())?;
+
+    let (digest, _) = dw.disclose();
+    let mut res = [0u8; DIGESTSIZE];
+    res.copy_from_slice(&digest.result());
+    Ok(res)
+}
+
+impl MasterSigningKey {
+    /// Generate the verifying key and the master signing key of a PKG.
+    pub fn setup<R: Rng + CryptoRng>(rng: &mut R) -> (MasterVerifyingKey, MasterSigningKey) {
+        let key = generate_signing_key(rng);
+        (
+            MasterVerifyingKey(key.verifying_key()),
+            MasterSigningKey(key),
+        )
+    }
+
+    /// Issue a signing key for the identity of a sender, which is certified by this key.
+    ///
+    /// Throws a ConstraintViolation when the identity can not be encoded.
+    pub fn issue<R: Rng + CryptoRng>(
+        &self,
+        identity: &Identity,
+        rng: &mut R,
+    ) -> Result<SenderKey, Error> {
+        let key = generate_signing_key(rng);
+        let verifying_key = key.verifying_key();
+        let digest = certificate_digest(identity, &verifying_key)?;
+
+        Ok(SenderKey {
+            key,
+            certificate: SenderCertificate {
+                identity: identity.clone(),
+                key: verifying_key,
+                signature: self.0.sign(&digest),
+            },
+        })
+    }
+
+    /// The verifying key with which the certificates issued by this key are verified.
+    pub fn verifying_key(&self) -> MasterVerifyingKey {
+        MasterVerifyingKey(self.0.verifying_key())
+    }
+
+    /// The byte representation, which should be overwritten with zeroes after use.
+    pub fn to_bytes(&self) -> [u8; SIGNING_KEY_SIZE] {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        if b.len() != SIGNING_KEY_SIZE {
+            return None;
+        }
+
+        Some(MasterSigningKey(SigningKey::from_bytes(array_ref![
+            b,
+            0,
+            SIGNING_KEY_SIZE
+        ])))
+    }
+}
+
+impl MasterVerifyingKey {
+    pub fn to_bytes(&self) -> [u8; VERIFYING_KEY_SIZE] {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        if b.len() != VERIFYING_KEY_SIZE {
+            return None;
+        }
+
+        VerifyingKey::from_bytes(array_ref![b, 0, VERIFYING_KEY_SIZE])
+            .ok()
+            .map(MasterVerifyingKey)
+    }
+}
+
+/// Serialize to a base64 encoded byte representation.
+impl Serialize for MasterVerifyingKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(self.to_bytes()))
+    }
+}
+
+/// Deserialize from a base64 encoded byte representation.
+impl<'de> Deserialize<'de> for MasterVerifyingKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <&'de str>::deserialize(deserializer)?;
+        let b = base64::decode(s).map_err(serde::de::Error::custom)?;
+
+        MasterVerifyingKey::from_bytes(&b)
+            .ok_or_else(|| serde::de::Error::custom("Not a valid verifying key"))
+    }
+}
+
+impl SenderCertificate {
+    /// The identity of the sender, which is only authentic if the certificate is issued by the PKG.
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Whether this certificate was issued by the PKG with the verifying key.
+    pub fn is_issued_by(&self, mvk: &MasterVerifyingKey) -> bool {
+        match certificate_digest(&self.identity, &self.key) {
+            Ok(digest) => mvk.0.verify_strict(&digest, &self.signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Read the value of a sender field.
+    pub(crate) fn read_from<R: Readable>(r: &mut R) -> Result<SenderCertificate, Error> {
+        let identity = Identity::read_from(r)?;
+        let key = VerifyingKey::from_bytes(array_ref![
+            r.read_bytes_strict(VERIFYING_KEY_SIZE)?,
+            0,
+            VERIFYING_KEY_SIZE
+        ])
+        .or(Err(Error::FormatViolation))?;
+        let signature = Signature::from_bytes(array_ref![
+            r.read_bytes_strict(SIGNATURE_SIZE)?,
+            0,
+            SIGNATURE_SIZE
+        ]);
+
+        Ok(SenderCertificate {
+            identity,
+            key,
+            signature,
+        })
+    }
+
+    /// Write the value of a sender field.
+    pub(crate) fn write_to<W: Writable>(&self, w: &mut W) -> Result<(), Error> {
+        self.identity.write_to(w)?;
+        w.write(self.key.as_bytes())?;
+        w.write(&self.signature.to_bytes())
+    }
+}
+
+impl SenderKey {
+    /// The certificate of the PKG for the identity of the sender.
+    pub fn certificate(&self) -> &SenderCertificate {
+        &self.certificate
+    }
+}
+
+impl core::fmt::Debug for SenderKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SenderKey")
+            .field("certificate", &self.certificate)
+            .finish()
+    }
+}
+
+/// Serialize to a base64 encoded byte representation, which consists of the signing key and the certificate.
+impl Serialize for SenderKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut b = ArrayVec::<[u8; 1024]>::new();
+        let res = b
+            .write(&self.key.to_bytes())
+            .and_then(|_| self.certificate.write_to(&mut b));
+        let encoded = res.map(|_| base64::encode(&b[..]));
+        b.as_mut_slice().zeroize();
+
+        serializer.serialize_str(&encoded.map_err(serde::ser::Error::custom)?)
+    }
+}
+
+/// Deserialize from a base64 encoded byte representation.
+impl<'de> Deserialize<'de> for SenderKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <&'de str>::deserialize(deserializer)?;
+        let mut b = base64::decode(s).map_err(serde::de::Error::custom)?;
+        let key = read_sender_key(&b);
+        b.as_mut_slice().zeroize();
+
+        key.ok_or_else(|| serde::de::Error::custom("Not a valid sender key"))
+    }
+}
+
+fn read_sender_key(b: &[u8]) -> Option<SenderKey> {
+    if b.len() < SIGNING_KEY_SIZE {
+        return None;
+    }
+
+    let key = SigningKey::from_bytes(array_ref![b, 0, SIGNING_KEY_SIZE]);
+    let mut r = crate::util::SliceReader::new(&b[SIGNING_KEY_SIZE..]);
+    let certificate = SenderCertificate::read_from(&mut r).ok()?;
+
+    // The certificate has to belong to the signing key.
+    if r.remaining() != 0 || certificate.key != key.verifying_key() {
+        return None;
+    }
+
+    Some(SenderKey { key, certificate })
+}
+
+/// The digest of the header and the sealed body, which is signed by the sender.
+struct Transcript(HeaderDigest);
+
+impl Transcript {
+    fn new(headerdigest: &[u8; DIGESTSIZE]) -> Transcript {
+        let mut digest = HeaderDigest::new();
+        digest.input(TRANSCRIPT_DOMAIN);
+        digest.input(headerdigest);
+        Transcript(digest)
+    }
+
+    fn result(&self) -> [u8; DIGESTSIZE] {
+        let mut res = [0u8; DIGESTSIZE];
+        res.copy_from_slice(&self.0.clone().result());
+        res
+    }
+}
+
+/// Signs a stream whilst its body is sealed.
+pub(crate) struct BodySigner<'a> {
+    key: &'a SenderKey,
+    transcript: Transcript,
+}
+
+impl<'a> BodySigner<'a> {
+    /// The digest of the header is authenticated with every segment.
+    pub fn new(key: &'a SenderKey, headerdigest: &[u8; DIGESTSIZE]) -> BodySigner<'a> {
+        BodySigner {
+            key,
+            transcript: Transcript::new(headerdigest),
+        }
+    }
+
+    /// Feed the sealed body, as it is written.
+    pub fn input(&mut self, sealed: &[u8]) {
+        self.transcript.0.input(sealed);
+    }
+
+    /// The signature that trails the body.
+    pub fn sign(self) -> [u8; SIGNATURE_SIZE] {
+        self.key.key.sign(&self.transcript.result()).to_bytes()
+    }
+}
+
+/// Verifies the signature of a stream whilst its body is opened.
+///
+/// Holds back the last `SIGNATURE_SIZE` bytes of the reader, such that only the body is read.
+pub(crate) struct SignedBody {
+    certificate: SenderCertificate,
+    transcript: Transcript,
+    /// The last bytes that were read, which turn out to be the signature once the reader is exhausted.
+    trailer: [u8; SIGNATURE_SIZE],
+    len: usize,
+    verified: bool,
+}
+
+impl SignedBody {
+    pub fn new(certificate: SenderCertificate, headerdigest: &[u8; DIGESTSIZE]) -> SignedBody {
+        SignedBody {
+            certificate,
+            transcript: Transcript::new(headerdigest),
+            trailer: [0u8; SIGNATURE_SIZE],
+            len: 0,
+            verified: false,
+        }
+    }
+
+    /// Read from `r` until `buf` is full or only the signature remains, and yield the number of bytes read.
+    pub fn read_fill<R: Readable>(&mut self, r: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.len < SIGNATURE_SIZE {
+            self.len += crate::stream::util::read_fill(r, &mut self.trailer[self.len..])?;
+            if self.len < SIGNATURE_SIZE {
+                return Ok(0);
+            }
+        }
+
+        let n = crate::stream::util::read_fill(r, buf)?;
+
+        // The trailer and the bytes that were read are shifted by `n` bytes.
+        if n >= SIGNATURE_SIZE {
+            let mut trailer = [0u8; SIGNATURE_SIZE];
+            trailer.copy_from_slice(&buf[n - SIGNATURE_SIZE..n]);
+            buf.copy_within(..n - SIGNATURE_SIZE, SIGNATURE_SIZE);
+            buf[..SIGNATURE_SIZE].copy_from_slice(&self.trailer);
+            self.trailer = trailer;
+        } else {
+            let mut read = [0u8; SIGNATURE_SIZE];
+            read[..n].copy_from_slice(&buf[..n]);
+            buf[..n].copy_from_slice(&self.trailer[..n]);
+            self.trailer.rotate_left(n);
+            self.trailer[SIGNATURE_SIZE - n..].copy_from_slice(&read[..n]);
+        }
+
+        Ok(n)
+    }
+
+    /// Feed the sealed body, as it is read.
+    pub fn input(&mut self, sealed: &[u8]) {
+        self.transcript.0.input(sealed);
+    }
+
+    /// Verify the signature once the entire body is read.
+    pub fn verify(&mut self) -> bool {
+        let signature = Signature::from_bytes(&self.trailer);
+        self.verified = self.len == SIGNATURE_SIZE
+            && self
+                .certificate
+                .key
+                .verify_strict(&self.transcript.result(), &signature)
+                .is_ok();
+        self.verified
+    }
+
+    /// The certificate of the sender, once the signature has been verified.
+    pub fn sender(&self) -> Option<&SenderCertificate> {
+        Some(&self.certificate).filter(|_| self.verified)
+    }
+}
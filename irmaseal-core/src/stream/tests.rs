@@ -34,31 +34,36 @@ fn seal(props: &DefaultProps, content: &[u8]) -> BigBuf {
     let DefaultProps { i, pk, sk: _ } = props;
 
     let mut buf = BigBuf::new();
-    {
-        let mut s = Sealer::new(&i, &PublicKey(pk.clone()), &mut rng, &mut buf).unwrap();
-        s.write(&content).unwrap();
-    } // Force Drop of s.
+    let mut s = Sealer::new(&i, &PublicKey::from(pk.clone()), &mut rng, &mut buf).unwrap();
+    s.write(&content).unwrap();
+    s.finish().unwrap();
 
     buf
 }
 
 fn unseal(props: &DefaultProps, buf: &[u8]) -> (BigBuf, bool) {
+    let DefaultProps { i, pk: _, sk: _ } = props;
+
+    unseal_for(props, i, buf)
+}
+
+fn unseal_for(props: &DefaultProps, i: &Identity, buf: &[u8]) -> (BigBuf, bool) {
     let mut rng = rand::thread_rng();
-    let DefaultProps { i, pk, sk } = props;
+    let DefaultProps { i: _, pk, sk } = props;
 
     let bufr = SliceReader::new(&buf);
-    let (i2, o) = OpenerSealed::new(bufr).unwrap();
+    let o = OpenerSealed::new(bufr).unwrap();
 
-    assert_eq!(i, &i2);
+    assert!(o.recipients().iter().any(|r| r.matches(i)));
 
-    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i2.derive(), &mut rng);
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
 
-    let mut o = o.unseal(&UserSecretKey(usk)).unwrap();
+    let mut o = o.unseal(i, &UserSecretKey::from(usk)).unwrap();
 
     let mut dst = BigBuf::new();
-    o.write_to(&mut dst).unwrap();
+    let res = o.write_to(&mut dst);
 
-    (dst, o.validate())
+    (dst, res.is_ok() && o.validate())
 }
 
 fn seal_and_unseal(props: &DefaultProps, content: &[u8]) -> (BigBuf, bool) {
@@ -87,6 +92,61 @@ fn reflection_sealer_opener() {
     do_test(&props, &mut [0u8; 60000]);
 }
 
+#[test]
+fn finish() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 1000];
+    rng.fill_bytes(&mut content);
+
+    let pk = PublicKey::from(props.pk);
+    let mut s = Sealer::new(&props.i, &pk, &mut rng, BigBuf::new()).unwrap();
+    s.write(&content).unwrap();
+    let (buf, len) = s.finish().unwrap();
+
+    assert_eq!(len, buf.len() as u64);
+
+    let (dst, valid) = unseal(&props, &buf);
+    assert_eq!(&content.as_ref(), &dst.as_slice());
+    assert!(valid);
+}
+
+#[test]
+fn unfinished() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 1000];
+    rng.fill_bytes(&mut content);
+
+    let pk = PublicKey::from(props.pk);
+    let mut buf = BigBuf::new();
+    {
+        let mut s = Sealer::new(&props.i, &pk, &mut rng, &mut buf).unwrap();
+        s.write(&content).unwrap();
+    }
+
+    let (dst, valid) = unseal(&props, &buf);
+    assert!(content.starts_with(&dst));
+    assert!(!valid);
+}
+
+#[test]
+fn failing_writer() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let pk = PublicKey::from(props.pk);
+    let buf = ArrayVec::<[u8; 1024]>::new();
+    let mut s = Sealer::new(&props.i, &pk, &mut rng, buf).unwrap();
+
+    assert!(matches!(
+        s.write(&[0u8; 2048]),
+        Err(Error::UpstreamWritableError)
+    ));
+}
+
 #[test]
 fn corrupt_body() {
     let props = DefaultProps::default();
@@ -98,12 +158,14 @@ fn corrupt_body() {
     buf[1000] += 0x02;
     let (dst, valid) = unseal(&props, &buf);
 
-    assert_ne!(&content.as_ref(), &dst.as_slice());
+    // Only the segments before the corrupted one are released.
+    assert!(dst.len() < 1000);
+    assert!(content.starts_with(&dst));
     assert!(!valid);
 }
 
 #[test]
-fn corrupt_hmac() {
+fn corrupt_tag() {
     let props = DefaultProps::default();
 
     let mut content = [0u8; 60000];
@@ -114,6 +176,2203 @@ fn corrupt_hmac() {
     buf[mutation_point] += 0x02;
     let (dst, valid) = unseal(&props, &buf);
 
-    assert_eq!(&content.as_ref(), &dst.as_slice());
+    assert_ne!(&content.as_ref(), &dst.as_slice());
+    assert!(content.starts_with(&dst));
+    assert!(!valid);
+}
+
+#[test]
+fn truncated() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let buf = seal(&props, &content);
+    let lastlen = (content.len() % BLOCKSIZE) + TAGSIZE;
+
+    // Drop the final segment entirely, such that the stream ends on a segment boundary.
+    let (dst, valid) = unseal(&props, &buf[..buf.len() - lastlen]);
+    assert!(content.starts_with(&dst));
+    assert!(!valid);
+
+    // Cut the stream halfway through the final segment.
+    let (dst, valid) = unseal(&props, &buf[..buf.len() - lastlen / 2]);
+    assert!(content.starts_with(&dst));
+    assert!(!valid);
+}
+
+#[test]
+fn reordered() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 2048];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let mut buf = seal(&props, &content);
+    let start = buf.len() - TAGSIZE - 3 * SEGMENTSIZE;
+    let mut segment = [0u8; SEGMENTSIZE];
+    segment.copy_from_slice(&buf[start..start + SEGMENTSIZE]);
+    buf.copy_within(start + SEGMENTSIZE..start + 2 * SEGMENTSIZE, start);
+    buf[start + SEGMENTSIZE..start + 2 * SEGMENTSIZE].copy_from_slice(&segment);
+
+    let (dst, valid) = unseal(&props, &buf);
+    assert!(content.starts_with(&dst));
     assert!(!valid);
 }
+
+#[test]
+fn master_secret_key() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let (pk, sk) = SecretKey::<kem::KiltzVahlisOne>::setup(&mut rng);
+    let sk = SecretKey::<kem::KiltzVahlisOne>::from_bytes(sk.to_bytes().as_ref()).unwrap();
+    let usk = sk.extract_usk(&pk, &props.i, &mut rng);
+
+    let mut content = [0u8; 1000];
+    rng.fill_bytes(&mut content);
+
+    let mut buf = BigBuf::new();
+    let mut s = Sealer::new(&props.i, &pk, &mut rng, &mut buf).unwrap();
+    s.write(&content).unwrap();
+    s.finish().unwrap();
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let mut o = o.unseal(&props.i, &usk).unwrap();
+
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(&content.as_ref(), &dst.as_slice());
+    assert!(o.validate());
+}
+
+fn recipients() -> [Identity; 3] {
+    [
+        Identity::new(
+            1566722350,
+            "pbdf.pbdf.email.email",
+            Some("alice@example.com"),
+        )
+        .unwrap(),
+        Identity::new(1566722350, "pbdf.pbdf.email.email", Some("bob@example.com")).unwrap(),
+        Identity::new(
+            1566722350,
+            "pbdf.gemeente.personalData.bsn",
+            Some("123456782"),
+        )
+        .unwrap(),
+    ]
+}
+
+#[test]
+fn multiple_recipients() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let recipients = recipients();
+
+    let mut content = [0u8; 1000];
+    rng.fill_bytes(&mut content);
+
+    let mut buf = BigBuf::new();
+    let pk = PublicKey::from(props.pk);
+    let mut s = Sealer::with_recipients(&recipients, &pk, &mut rng, &mut buf).unwrap();
+    s.write(&content).unwrap();
+    s.finish().unwrap();
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.recipients().len(), recipients.len());
+    for (r, i) in o.recipients().iter().zip(recipients.iter()) {
+        assert_eq!(r.identity(), i);
+    }
+
+    for i in recipients.iter() {
+        let (dst, valid) = unseal_for(&props, i, &buf);
+
+        assert_eq!(&content.as_ref(), &dst.as_slice());
+        assert!(valid);
+    }
+}
+
+#[test]
+fn earliest_timestamp() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let buf = seal(&props, &[0u8; 16]);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.earliest_timestamp(), props.i.timestamp);
+
+    // A stream for recipients with future timestamps is released at the earliest of them.
+    let mut recipients = recipients();
+    recipients[0].timestamp = 4102444800;
+    recipients[1].timestamp = 4070908800;
+    recipients[2].timestamp = 4133980800;
+
+    let mut buf = BigBuf::new();
+    let pk = PublicKey::from(props.pk);
+    let mut s = Sealer::with_recipients(&recipients, &pk, &mut rng, &mut buf).unwrap();
+    s.write(&[0u8; 16]).unwrap();
+    s.finish().unwrap();
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.earliest_timestamp(), 4070908800);
+
+    let (dst, valid) = unseal_for(&props, &recipients[1], &buf);
+    assert_eq!(&[0u8; 16][..], dst.as_slice());
+    assert!(valid);
+}
+
+#[test]
+fn timestamp_policy() {
+    assert_eq!(
+        TimestampPolicy::Exact.round_down(1566722350),
+        Some(1566722350)
+    );
+    assert_eq!(
+        TimestampPolicy::Day.round_down(1566722350),
+        Some(1566691200)
+    );
+    assert_eq!(
+        TimestampPolicy::Day.round_down(1566691200),
+        Some(1566691200)
+    );
+    assert_eq!(
+        TimestampPolicy::Week.round_down(1566722350),
+        Some(1566172800)
+    );
+    assert_eq!(
+        TimestampPolicy::Week.round_down(1566172800),
+        Some(1566172800)
+    );
+    assert_eq!(TimestampPolicy::Week.round_down(0), Some(0));
+
+    assert_eq!(
+        TimestampPolicy::Exact.round_up(1566722350),
+        Some(1566722350)
+    );
+    assert_eq!(TimestampPolicy::Day.round_up(1566722350), Some(1566777600));
+    assert_eq!(TimestampPolicy::Day.round_up(1566691200), Some(1566691200));
+    assert_eq!(TimestampPolicy::Week.round_up(1566722350), Some(1566777600));
+    assert_eq!(TimestampPolicy::Week.round_up(1566172800), Some(1566172800));
+    assert_eq!(TimestampPolicy::Week.round_up(1), Some(4 * 24 * 60 * 60));
+
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let options = SealOptions {
+        timestamp_policy: TimestampPolicy::Day,
+        ..Default::default()
+    };
+
+    // A single key unseals every stream that is sealed for the recipient on the same day.
+    let epoch = Identity {
+        timestamp: 1566691200,
+        ..props.i.clone()
+    };
+    let usk = UserSecretKey::from(ibe::kiltz_vahlis_one::extract_usk(
+        &props.pk,
+        &props.sk,
+        &epoch.derive(),
+        &mut rng,
+    ));
+
+    for timestamp in [1566722350, 1566777599].iter() {
+        let i = Identity {
+            timestamp: *timestamp,
+            ..props.i.clone()
+        };
+
+        let mut buf = BigBuf::new();
+        let mut s = Sealer::with_options(
+            core::slice::from_ref(&i),
+            &options,
+            &PublicKey::from(props.pk),
+            &mut rng,
+            &mut buf,
+        )
+        .unwrap();
+        s.write(&[0u8; 16]).unwrap();
+        s.finish().unwrap();
+
+        let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+        assert_eq!(o.timestamp_policy(), TimestampPolicy::Day);
+        assert_eq!(o.recipients()[0].identity(), &epoch);
+        assert_eq!(o.earliest_timestamp(), 1566691200);
+
+        let mut o = o.unseal(&epoch, &usk).unwrap();
+        let mut dst = BigBuf::new();
+        o.write_to(&mut dst).unwrap();
+        assert_eq!(&[0u8; 16][..], dst.as_slice());
+        assert!(o.validate());
+    }
+
+    let buf = seal(&props, &[0u8; 16]);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.timestamp_policy(), TimestampPolicy::Exact);
+}
+
+#[test]
+fn timestamp_policy_future() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    // Time-released at 2100-01-01T03:25:45Z, which lies on a Friday.
+    let requested = 4102457145;
+
+    // The key may not become available before the sender asked for, hence the caller rounds the
+    // timestamp up, which the sealer leaves as is.
+    for (policy, expected) in [
+        (TimestampPolicy::Day, 4102531200),
+        (TimestampPolicy::Week, 4102704000),
+    ]
+    .iter()
+    {
+        let i = Identity {
+            timestamp: policy.round_up(requested).unwrap(),
+            ..props.i.clone()
+        };
+        assert_eq!(i.timestamp, *expected);
+
+        let options = SealOptions {
+            timestamp_policy: *policy,
+            ..Default::default()
+        };
+        let mut buf = BigBuf::new();
+        let mut s = Sealer::with_options(
+            core::slice::from_ref(&i),
+            &options,
+            &PublicKey::from(props.pk),
+            &mut rng,
+            &mut buf,
+        )
+        .unwrap();
+        s.write(&[0u8; 16]).unwrap();
+        s.finish().unwrap();
+
+        let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+        assert_eq!(o.recipients()[0].identity().timestamp, *expected);
+        assert!(o.earliest_timestamp() >= requested);
+    }
+}
+
+#[test]
+fn timestamp_policy_overflow() {
+    let mut props = DefaultProps::default();
+    let options = SealOptions {
+        timestamp_policy: TimestampPolicy::Week,
+        ..Default::default()
+    };
+
+    assert_eq!(TimestampPolicy::Week.round_down(u64::MAX), None);
+    assert_eq!(TimestampPolicy::Week.round_up(u64::MAX), None);
+    assert_eq!(TimestampPolicy::Day.round_up(u64::MAX), None);
+
+    // The start of the week of a timestamp that can not be represented can not be sealed for.
+    let i = Identity {
+        timestamp: u64::MAX,
+        ..props.i.clone()
+    };
+    let mut rng = rand::thread_rng();
+    let mut buf = BigBuf::new();
+    assert!(matches!(
+        Sealer::with_options(
+            core::slice::from_ref(&i),
+            &options,
+            &PublicKey::from(props.pk),
+            &mut rng,
+            &mut buf,
+        ),
+        Err(Error::ConstraintViolation)
+    ));
+
+    // Nor is it accepted from the header.
+    props.i.timestamp = 1566172800;
+    let buf = seal_with(&props, &options, &[0u8; 16]);
+    let pos = buf
+        .windows(8)
+        .position(|w| w == 1566172800u64.to_be_bytes())
+        .unwrap();
+    let mut corrupted = buf.clone();
+    corrupted[pos..pos + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&corrupted)),
+        Err(Error::FormatViolation)
+    ));
+}
+
+#[test]
+fn corrupt_timestamp_policy() {
+    // On a Thursday, such that neither rounding lands on the start of a week.
+    let mut props = DefaultProps::default();
+    props.i.timestamp = 1566463150;
+    let options = SealOptions {
+        timestamp_policy: TimestampPolicy::Day,
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &[0u8; 16]);
+    let pos = buf.windows(3).position(|w| w == [0x0E, 0x00, 1]).unwrap() + 3;
+    assert_eq!(buf[pos], 0x01);
+
+    // The timestamps of the recipients have to be rounded as the policy claims, and the policy has to be known.
+    for policy in [0x02, 0x00, 0xFF].iter() {
+        let mut corrupted = buf.clone();
+        corrupted[pos] = *policy;
+        assert!(matches!(
+            OpenerSealed::new(SliceReader::new(&corrupted)),
+            Err(Error::FormatViolation)
+        ));
+    }
+}
+
+#[test]
+fn unknown_recipient() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let buf = seal(&props, &[0u8; 16]);
+    let other = &recipients()[0];
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &other.derive(), &mut rng);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal(other, &UserSecretKey::from(usk)),
+        Err(Error::UnknownRecipient)
+    ));
+}
+
+#[test]
+fn wrong_key() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let buf = seal(&props, &[0u8; 16]);
+
+    // The key of another identity, and the key of the identity under another master key.
+    let other = &recipients()[0];
+    let (other_pk, other_sk) = ibe::kiltz_vahlis_one::setup(&mut rng);
+    let usks = [
+        ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &other.derive(), &mut rng),
+        ibe::kiltz_vahlis_one::extract_usk(&other_pk, &other_sk, &props.i.derive(), &mut rng),
+    ];
+
+    for usk in usks.iter() {
+        let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+        assert!(matches!(
+            o.unseal(&props.i, &UserSecretKey::from(*usk)),
+            Err(Error::WrongKey)
+        ));
+    }
+}
+
+#[test]
+fn too_many_recipients() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+    let mut buf = BigBuf::new();
+
+    assert!(matches!(
+        Sealer::with_recipients(&[], &pk, &mut rng, &mut buf),
+        Err(Error::ConstraintViolation)
+    ));
+
+    let recipients: ArrayVec<[Identity; MAX_RECIPIENTS + 1]> =
+        (0..=MAX_RECIPIENTS).map(|_| props.i.clone()).collect();
+
+    let mut buf = BigBuf::new();
+    assert!(matches!(
+        Sealer::with_recipients(&recipients, &pk, &mut rng, &mut buf),
+        Err(Error::ConstraintViolation)
+    ));
+
+    let mut buf = BigBuf::new();
+    assert!(
+        Sealer::with_recipients(&recipients[..MAX_RECIPIENTS], &pk, &mut rng, &mut buf).is_ok()
+    );
+}
+
+fn seal_metadata(props: &DefaultProps, metadata: &Metadata, content: &[u8]) -> BigBuf {
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+    let options = SealOptions {
+        metadata: Some(metadata.clone()),
+        ..Default::default()
+    };
+
+    let mut buf = BigBuf::new();
+    let mut s =
+        Sealer::with_options(&[props.i.clone()], &options, &pk, &mut rng, &mut buf).unwrap();
+    s.write(content).unwrap();
+    s.finish().unwrap();
+
+    buf
+}
+
+#[test]
+fn metadata() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+
+    let metadata = Metadata {
+        file_name: Some(arrayvec::ArrayString::from("report.pdf").unwrap()),
+        mime_type: Some(arrayvec::ArrayString::from("application/pdf").unwrap()),
+        size: Some(1000),
+        modified: Some(1566722350),
+    };
+
+    let mut content = [0u8; 1000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let buf = seal_metadata(&props, &metadata, &content);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let o = o.unseal(&props.i, &UserSecretKey::from(usk)).unwrap();
+    assert_eq!(o.metadata(), Some(&metadata));
+
+    let (dst, valid) = unseal(&props, &buf);
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(valid);
+
+    let buf = seal(&props, &content);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let o = o.unseal(&props.i, &UserSecretKey::from(usk)).unwrap();
+    assert_eq!(o.metadata(), None);
+}
+
+fn reseal_for(
+    props: &DefaultProps,
+    to: &Identity,
+    buf: &[u8],
+    dst: &mut BigBuf,
+) -> Result<u64, Error> {
+    let mut rng = rand::thread_rng();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+
+    let o = OpenerSealed::new(SliceReader::new(buf)).unwrap();
+    let o = o.unseal(&props.i, &UserSecretKey::from(usk)).unwrap();
+
+    let options = SealOptions {
+        metadata: o.metadata().cloned(),
+        ..Default::default()
+    };
+    let pk = PublicKey::from(props.pk);
+    let sealer = Sealer::with_options(core::slice::from_ref(to), &options, &pk, &mut rng, dst)?;
+
+    o.reseal(sealer).map(|(_, len)| len)
+}
+
+#[test]
+fn reseal() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+
+    let metadata = Metadata {
+        file_name: Some(arrayvec::ArrayString::from("report.pdf").unwrap()),
+        ..Default::default()
+    };
+    let mut content = [0u8; 3000];
+    rng.fill_bytes(&mut content);
+    let buf = seal_metadata(&props, &metadata, &content);
+
+    let successor = &recipients()[0];
+    let mut resealed = BigBuf::new();
+    let len = reseal_for(&props, successor, &buf, &mut resealed).unwrap();
+    assert_eq!(len, resealed.len() as u64);
+
+    let (dst, valid) = unseal_for(&props, successor, &resealed);
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(valid);
+
+    // The metadata is carried over, and the stream is no longer sealed for the original recipient.
+    let o = OpenerSealed::new(SliceReader::new(&resealed)).unwrap();
+    assert!(!o.recipients().iter().any(|r| r.matches(&props.i)));
+    let o = OpenerSealed::new(SliceReader::new(&resealed)).unwrap();
+    assert!(matches!(
+        o.unseal(&props.i, &UserSecretKey::from(usk)),
+        Err(Error::UnknownRecipient)
+    ));
+    let usk =
+        ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &successor.derive(), &mut rng);
+    let o = OpenerSealed::new(SliceReader::new(&resealed)).unwrap();
+    let o = o.unseal(successor, &UserSecretKey::from(usk)).unwrap();
+    assert_eq!(o.metadata(), Some(&metadata));
+
+    // A stream that is not authentic is never resealed into one that validates.
+    for pos in [buf.len() - 1, buf.len() - 2000].iter() {
+        let mut corrupted = buf.clone();
+        corrupted[*pos] ^= 0x01;
+
+        let mut resealed = BigBuf::new();
+        assert!(matches!(
+            reseal_for(&props, successor, &corrupted, &mut resealed),
+            Err(Error::IntegrityViolation)
+        ));
+
+        let (_, valid) = unseal_for(&props, successor, &resealed);
+        assert!(!valid);
+    }
+}
+
+#[test]
+fn corrupt_metadata() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+
+    let metadata = Metadata {
+        file_name: Some(arrayvec::ArrayString::from("report.pdf").unwrap()),
+        ..Default::default()
+    };
+
+    let mut buf = seal_metadata(&props, &metadata, b"content");
+
+    // The sealed metadata is the last field of the header.
+    let len = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]) as usize;
+    buf[9 + len - 1] ^= 0x01;
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal(&props.i, &UserSecretKey::from(usk)),
+        Err(Error::IntegrityViolation)
+    ));
+}
+
+#[test]
+fn hidden_recipients() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+    let recipients = recipients();
+    let options = SealOptions {
+        hide_values: true,
+        ..Default::default()
+    };
+
+    let mut content = [0u8; 1000];
+    rng.fill_bytes(&mut content);
+
+    let mut buf = BigBuf::new();
+    let mut s = Sealer::with_options(&recipients, &options, &pk, &mut rng, &mut buf).unwrap();
+    s.write(&content).unwrap();
+    s.finish().unwrap();
+
+    for i in recipients.iter() {
+        let value = i.attribute.value.unwrap();
+        assert!(!buf.windows(value.len()).any(|w| w == value.as_bytes()));
+    }
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    for (r, i) in o.recipients().iter().zip(recipients.iter()) {
+        assert!(r.is_hidden());
+        assert_eq!(r.identity().attribute.atype, i.attribute.atype);
+        assert_eq!(r.identity().attribute.value, None);
+        assert!(r.matches(i));
+    }
+
+    for i in recipients.iter() {
+        let (dst, valid) = unseal_for(&props, i, &buf);
+
+        assert_eq!(&content[..], dst.as_slice());
+        assert!(valid);
+    }
+
+    let other = Identity::new(
+        recipients[0].timestamp,
+        &recipients[0].attribute.atype,
+        Some("someone.else@example.com"),
+    )
+    .unwrap();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &other.derive(), &mut rng);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    if !o.recipients().iter().any(|r| r.matches(&other)) {
+        assert!(matches!(
+            o.unseal(&other, &UserSecretKey::from(usk)),
+            Err(Error::UnknownRecipient)
+        ));
+    }
+}
+
+#[test]
+fn hidden_recipients_collision() {
+    use core::fmt::Write;
+    use rand::SeedableRng;
+
+    let props = DefaultProps::default();
+    let pk = PublicKey::from(props.pk);
+    let alice = recipients()[0].clone();
+    let options = SealOptions {
+        hide_values: true,
+        ..Default::default()
+    };
+
+    let seal_for = |other: &Identity| {
+        // The same seed yields the same ciphertext, and thus the same hint, for the first recipient.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut buf = BigBuf::new();
+        let mut s = Sealer::with_options(
+            &[alice.clone(), other.clone()],
+            &options,
+            &pk,
+            &mut rng,
+            &mut buf,
+        )
+        .unwrap();
+        s.write(&[0u8; 16]).unwrap();
+        s.finish().unwrap();
+
+        buf
+    };
+
+    // Find another recipient of which the identity matches the hint of the first recipient.
+    let buf = seal_for(&recipients()[1]);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let other = (0..)
+        .map(|n| {
+            let mut value = arrayvec::ArrayString::<[u8; 32]>::new();
+            write!(value, "user{}@example.com", n).unwrap();
+            Identity::new(alice.timestamp, &alice.attribute.atype, Some(&value)).unwrap()
+        })
+        .find(|i| o.recipients()[0].matches(i))
+        .unwrap();
+
+    let buf = seal_for(&other);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(o.recipients().iter().all(|r| r.matches(&other)));
+
+    // Both recipients are tried, of which only the second one unseals the stream.
+    let (dst, valid) = unseal_for(&props, &other, &buf);
+    assert_eq!(&[0u8; 16][..], dst.as_slice());
+    assert!(valid);
+
+    let (dst, valid) = unseal_for(&props, &alice, &buf);
+    assert_eq!(&[0u8; 16][..], dst.as_slice());
+    assert!(valid);
+}
+
+fn seal_with(props: &DefaultProps, options: &SealOptions, content: &[u8]) -> BigBuf {
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+
+    let mut buf = BigBuf::new();
+    let mut s = Sealer::with_options(
+        core::slice::from_ref(&props.i),
+        options,
+        &pk,
+        &mut rng,
+        &mut buf,
+    )
+    .unwrap();
+    s.write(content).unwrap();
+    s.finish().unwrap();
+
+    buf
+}
+
+fn seal_padded(props: &DefaultProps, padding: Padding, content: &[u8]) -> BigBuf {
+    let options = SealOptions {
+        padding,
+        ..Default::default()
+    };
+
+    seal_with(props, &options, content)
+}
+
+/// The number of segments in the body of a sealed stream.
+fn body_segments(buf: &[u8]) -> usize {
+    let len = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]) as usize;
+    let body = buf.len() - 9 - len;
+
+    assert_eq!(body % SEGMENTSIZE, 0);
+    body / SEGMENTSIZE
+}
+
+#[test]
+fn padding() {
+    let props = DefaultProps::default();
+
+    let mut contentbuf = [0u8; 20000];
+
+    for len in [0, 1, 508, 509, 1016, 3000, 20000].iter() {
+        let content = &mut contentbuf[..*len];
+        rand::thread_rng().fill_bytes(content);
+
+        let buf = seal_padded(&props, Padding::PowerOfTwo, content);
+        assert!(body_segments(&buf).is_power_of_two());
+
+        let (dst, valid) = unseal(&props, &buf);
+        assert_eq!(&content[..], dst.as_slice());
+        assert!(valid);
+
+        let buf = seal_padded(&props, Padding::Bucket(4096), content);
+        assert_eq!(body_segments(&buf) % 8, 0);
+
+        let (dst, valid) = unseal(&props, &buf);
+        assert_eq!(&content[..], dst.as_slice());
+        assert!(valid);
+
+        let buf = seal_padded(&props, Padding::Random(10000), content);
+        let minimum = core::cmp::max(1, len.div_ceil(BLOCKSIZE - PADDING_TRAILERSIZE));
+        assert!(body_segments(&buf) >= minimum);
+        assert!(body_segments(&buf) <= minimum + 19);
+
+        let (dst, valid) = unseal(&props, &buf);
+        assert_eq!(&content[..], dst.as_slice());
+        assert!(valid);
+    }
+}
+
+#[test]
+fn padding_hides_length() {
+    let props = DefaultProps::default();
+
+    let short = seal_padded(&props, Padding::Bucket(8192), &[0u8; 10]);
+    let long = seal_padded(&props, Padding::Bucket(8192), &[0u8; 5000]);
+
+    assert_eq!(short.len(), long.len());
+}
+
+#[test]
+fn truncated_padding() {
+    let props = DefaultProps::default();
+
+    let buf = seal_padded(&props, Padding::PowerOfTwo, &[0u8; 3000]);
+    let (_, valid) = unseal(&props, &buf[..buf.len() - SEGMENTSIZE]);
+
+    assert!(!valid);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compression() {
+    let props = DefaultProps::default();
+
+    let mut text = [0u8; 40000];
+    for (b, c) in text.iter_mut().zip(
+        b"the quick brown fox jumps over the lazy dog. "
+            .iter()
+            .cycle(),
+    ) {
+        *b = *c;
+    }
+
+    let mut random = [0u8; 20000];
+    rand::thread_rng().fill_bytes(&mut random);
+
+    let options = SealOptions {
+        compression: Compression::Deflate,
+        ..Default::default()
+    };
+
+    for content in [&text[..], &random[..], &text[..1], &[]].iter() {
+        let buf = seal_with(&props, &options, content);
+        let (dst, valid) = unseal(&props, &buf);
+
+        assert_eq!(*content, dst.as_slice());
+        assert!(valid);
+
+        let (dst, res) = unseal_io(&props, &buf);
+
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(*content, dst.as_slice());
+    }
+
+    let buf = seal_with(&props, &options, &text);
+    assert!(buf.len() < text.len() / 10);
+
+    let padded = SealOptions {
+        compression: Compression::Deflate,
+        padding: Padding::PowerOfTwo,
+        ..Default::default()
+    };
+
+    let buf = seal_with(&props, &padded, &text);
+    assert!(body_segments(&buf).is_power_of_two());
+
+    let (dst, valid) = unseal(&props, &buf);
+    assert_eq!(&text[..], dst.as_slice());
+    assert!(valid);
+}
+
+#[test]
+fn unsupported_compression() {
+    let props = DefaultProps::default();
+    let buf = seal(&props, &[0u8; 1000]);
+
+    let inserted = insert_field(&buf, &[0x87, 0x00, 0x01, 0x7F]);
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::IncorrectVersion)
+    ));
+}
+
+#[test]
+fn scheme() {
+    let props = DefaultProps::default();
+    let buf = seal(&props, &[0u8; 1000]);
+
+    let inserted = insert_field(&buf, &[0x88, 0x00, 0x01, 0x7F]);
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::IncorrectVersion)
+    ));
+
+    // The scheme is already recorded by the sealer, and may only occur once.
+    let inserted = insert_field(&buf, &[0x88, 0x00, 0x01, 0x01]);
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::FormatViolation)
+    ));
+}
+
+#[test]
+fn key_commitment() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let buf = seal(&props, &[0u8; 1000]);
+
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+    let usk = UserSecretKey::from(usk);
+
+    // The commitment directly follows the nonce prefix.
+    let pos = buf
+        .windows(3)
+        .position(|w| w == [0x82, 0x00, NONCE_PREFIX_SIZE as u8])
+        .unwrap()
+        + 3
+        + NONCE_PREFIX_SIZE;
+    assert_eq!(&buf[pos..pos + 3], &[0x8A, 0x00, KEYSIZE as u8]);
+
+    let mut corrupted = buf.clone();
+    corrupted[pos + 3] ^= 0x01;
+    let o = OpenerSealed::new(SliceReader::new(&corrupted)).unwrap();
+    assert!(matches!(o.unseal(&props.i, &usk), Err(Error::WrongKey)));
+
+    // Format version 1 was never released, and is not read.
+    let mut downgraded = buf.clone();
+    downgraded[4] = 0x01;
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&downgraded)),
+        Err(Error::IncorrectVersion)
+    ));
+}
+
+#[cfg(feature = "hybrid")]
+#[test]
+fn hybrid() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 5000];
+    rng.fill_bytes(&mut content);
+
+    let (hpk, hsk) = generate_hybrid_keys(&mut rng).unwrap();
+    let (_, other_hsk) = generate_hybrid_keys(&mut rng).unwrap();
+
+    let options = SealOptions {
+        hybrid: Some(std::vec![hpk.clone()]),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &content);
+
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+    let usk = UserSecretKey::from(usk);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(o.recipients().iter().all(|r| r.is_hybrid()));
+    assert_eq!(
+        o.unlock_methods().as_slice(),
+        &[UnlockMethod::HybridIdentity]
+    );
+    let mut o = o.unseal_hybrid(&props.i, &usk, &hsk).unwrap();
+
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(o.validate());
+
+    // Both the IBE key and the post-quantum key are required.
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal(&props.i, &usk),
+        Err(Error::IncorrectScheme)
+    ));
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal_hybrid(&props.i, &usk, &other_hsk),
+        Err(Error::WrongKey)
+    ));
+
+    let buf = seal(&props, &content);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal_hybrid(&props.i, &usk, &hsk),
+        Err(Error::IncorrectScheme)
+    ));
+
+    let options = SealOptions {
+        hybrid: Some(std::vec![hpk.clone(), hpk]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Sealer::with_options(
+            core::slice::from_ref(&props.i),
+            &options,
+            &PublicKey::from(props.pk),
+            &mut rng,
+            BigBuf::new(),
+        ),
+        Err(Error::ConstraintViolation)
+    ));
+}
+
+#[cfg(feature = "passphrase")]
+#[test]
+fn passphrase() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 3000];
+    rng.fill_bytes(&mut content);
+
+    // Keep the tests fast, with the least cost Argon2 accepts.
+    let cost = PassphraseCost {
+        memory: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+    let options = SealOptions {
+        passphrase: Some(Passphrase::with_cost("correct horse", cost)),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &content);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(
+        o.unlock_methods().as_slice(),
+        &[UnlockMethod::Identity, UnlockMethod::Passphrase]
+    );
+    let mut o = o.unseal_with_passphrase("correct horse").unwrap();
+
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(o.validate());
+
+    // The recipients can still unseal the stream themselves.
+    let (dst, valid) = unseal_for(&props, &props.i, &buf);
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(valid);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal_with_passphrase("battery staple"),
+        Err(Error::WrongKey)
+    ));
+
+    let plain = seal(&props, &content);
+    let o = OpenerSealed::new(SliceReader::new(&plain)).unwrap();
+    assert_eq!(o.unlock_methods().as_slice(), &[UnlockMethod::Identity]);
+    assert!(matches!(
+        o.unseal_with_passphrase("correct horse"),
+        Err(Error::UnknownRecipient)
+    ));
+}
+
+#[cfg(feature = "passphrase")]
+#[test]
+fn passphrase_cost() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let cost = PassphraseCost {
+        memory: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+    let options = SealOptions {
+        passphrase: Some(Passphrase::with_cost("correct horse", cost)),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &[0u8; 1000]);
+
+    // The cost is recorded in the header, and a header can not demand an excessive cost.
+    let pos = buf.windows(3).position(|w| w == [0x8C, 0x00, 60]).unwrap() + 3;
+    assert_eq!(&buf[pos..pos + 4], &8u32.to_be_bytes());
+    for (offset, value) in [
+        (0, u32::MAX),
+        (0, (1 << 17) + 1),
+        (4, u32::MAX),
+        (4, 17),
+        (8, u32::MAX),
+        (8, 9),
+    ]
+    .iter()
+    {
+        let mut corrupted = buf.clone();
+        corrupted[pos + offset..pos + offset + 4].copy_from_slice(&value.to_be_bytes());
+        assert!(matches!(
+            OpenerSealed::new(SliceReader::new(&corrupted)),
+            Err(Error::FormatViolation)
+        ));
+    }
+
+    // The default cost round trips as well.
+    let options = SealOptions {
+        passphrase: Some(Passphrase::new("correct horse")),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &[0u8; 1000]);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let mut o = o.unseal_with_passphrase("correct horse").unwrap();
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(&[0u8; 1000][..], dst.as_slice());
+    assert!(o.validate());
+
+    for cost in [
+        PassphraseCost {
+            parallelism: 0,
+            ..cost
+        },
+        PassphraseCost {
+            memory: (1 << 17) + 1,
+            ..cost
+        },
+        PassphraseCost {
+            iterations: 17,
+            ..cost
+        },
+        PassphraseCost {
+            parallelism: 9,
+            ..cost
+        },
+    ]
+    .iter()
+    {
+        let options = SealOptions {
+            passphrase: Some(Passphrase::with_cost("correct horse", *cost)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Sealer::with_options(
+                core::slice::from_ref(&props.i),
+                &options,
+                &PublicKey::from(props.pk),
+                &mut rng,
+                BigBuf::new(),
+            ),
+            Err(Error::ConstraintViolation)
+        ));
+    }
+}
+
+#[cfg(feature = "escrow")]
+#[test]
+fn escrow() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 3000];
+    rng.fill_bytes(&mut content);
+
+    let (epk, esk) = generate_escrow_keys(&mut rng);
+    let (_, other_esk) = generate_escrow_keys(&mut rng);
+    assert_eq!(esk.public_key(), epk);
+    assert_eq!(EscrowPublicKey::from_bytes(&epk.to_bytes()), Some(epk));
+    let esk = EscrowSecretKey::from_bytes(&esk.to_bytes()[..]).unwrap();
+
+    let options = SealOptions {
+        escrow: Some(epk),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &content);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.escrow(), Some(&epk));
+    assert_eq!(
+        o.unlock_methods().as_slice(),
+        &[UnlockMethod::Identity, UnlockMethod::Escrow]
+    );
+    let mut o = o.unseal_with_escrow(&esk).unwrap();
+
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(o.validate());
+
+    // The recipients can still unseal the stream themselves.
+    let (dst, valid) = unseal_for(&props, &props.i, &buf);
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(valid);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal_with_escrow(&other_esk),
+        Err(Error::WrongKey)
+    ));
+
+    let plain = seal(&props, &content);
+    let o = OpenerSealed::new(SliceReader::new(&plain)).unwrap();
+    assert_eq!(o.escrow(), None);
+    assert!(matches!(
+        o.unseal_with_escrow(&esk),
+        Err(Error::UnknownRecipient)
+    ));
+}
+
+#[cfg(feature = "escrow")]
+#[test]
+fn corrupt_escrow() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let (epk, esk) = generate_escrow_keys(&mut rng);
+    let options = SealOptions {
+        escrow: Some(epk),
+        ..Default::default()
+    };
+    let buf = seal_with(&props, &options, &[0u8; 1000]);
+
+    // The escrow field holds the escrow key, the ephemeral key and the wrapped body key.
+    let pos = buf.windows(3).position(|w| w == [0x8D, 0x00, 96]).unwrap() + 3;
+    assert_eq!(&buf[pos..pos + 32], &epk.to_bytes());
+
+    // A tampered wrapped key does not match the key commitment.
+    let mut corrupted = buf.clone();
+    corrupted[pos + 64] ^= 0x01;
+    let o = OpenerSealed::new(SliceReader::new(&corrupted)).unwrap();
+    assert!(matches!(o.unseal_with_escrow(&esk), Err(Error::WrongKey)));
+
+    // An ephemeral key of low order is rejected.
+    let mut corrupted = buf.clone();
+    corrupted[pos + 32..pos + 64].copy_from_slice(&[0u8; 32]);
+    let o = OpenerSealed::new(SliceReader::new(&corrupted)).unwrap();
+    assert!(matches!(
+        o.unseal_with_escrow(&esk),
+        Err(Error::FormatViolation)
+    ));
+}
+
+#[test]
+fn armor() {
+    use crate::armor::{ArmorReader, ArmorWriter};
+
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = [0u8; 10000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let w = ArmorWriter::new(BigBuf::new()).unwrap();
+    let mut s = Sealer::new(&props.i, &PublicKey::from(props.pk), &mut rng, w).unwrap();
+    s.write(&content).unwrap();
+    let (w, _) = s.finish().unwrap();
+    let buf = w.finish().unwrap();
+
+    assert!(crate::armor::is_armored(&buf));
+    assert!(buf.iter().all(|b| b.is_ascii()));
+
+    let r = ArmorReader::new(SliceReader::new(&buf)).unwrap();
+    let o = OpenerSealed::new(r).unwrap();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+    let mut o = o.unseal(&props.i, &UserSecretKey::from(usk)).unwrap();
+
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+
+    assert_eq!(&content[..], dst.as_slice());
+    assert!(o.validate());
+}
+
+#[test]
+fn armor_large_field() {
+    use crate::armor::{ArmorReader, ArmorWriter, MAX_READSIZE};
+
+    let props = DefaultProps::default();
+    let buf = seal(&props, &[0u8; 1000]);
+
+    let armor = |buf: &[u8]| {
+        let mut w = ArmorWriter::new(BigBuf::new()).unwrap();
+        w.write(buf).unwrap();
+        w.finish().unwrap()
+    };
+    let value = [0u8; MAX_READSIZE + 1000];
+    let len = (value.len() as u16).to_be_bytes();
+
+    // Unknown fields that exceed the reads of the armor are skipped, as in a plain stream.
+    let mut field = BigBuf::new();
+    field
+        .try_extend_from_slice(&[0x7F, len[0], len[1]])
+        .unwrap();
+    field.try_extend_from_slice(&value).unwrap();
+    let inserted = insert_field(&buf, &field);
+    let armored = armor(&inserted);
+
+    let o = OpenerSealed::new(SliceReader::new(&inserted)).unwrap();
+    assert_eq!(o.earliest_timestamp(), props.i.timestamp);
+    let r = ArmorReader::new(SliceReader::new(&armored)).unwrap();
+    let o = OpenerSealed::new(r).unwrap();
+    assert_eq!(o.earliest_timestamp(), props.i.timestamp);
+
+    // Oversized values of known fields are rejected before they are read.
+    let mut identity = BigBuf::new();
+    props.i.write_to(&mut identity).unwrap();
+    let len = ((identity.len() + value.len()) as u16).to_be_bytes();
+
+    let mut field = BigBuf::new();
+    field
+        .try_extend_from_slice(&[0x81, len[0], len[1]])
+        .unwrap();
+    field.try_extend_from_slice(&identity).unwrap();
+    field.try_extend_from_slice(&value).unwrap();
+    let inserted = insert_field(&buf, &field);
+    let armored = armor(&inserted);
+
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::FormatViolation)
+    ));
+    let r = ArmorReader::new(SliceReader::new(&armored)).unwrap();
+    assert!(matches!(OpenerSealed::new(r), Err(Error::FormatViolation)));
+
+    let len = (value.len() as u16).to_be_bytes();
+    let mut field = BigBuf::new();
+    field
+        .try_extend_from_slice(&[0x04, len[0], len[1]])
+        .unwrap();
+    field.try_extend_from_slice(&value).unwrap();
+    let inserted = insert_field(&buf, &field);
+    let armored = armor(&inserted);
+
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::FormatViolation)
+    ));
+    let r = ArmorReader::new(SliceReader::new(&armored)).unwrap();
+    assert!(matches!(OpenerSealed::new(r), Err(Error::FormatViolation)));
+}
+
+/// Insert a field at the start of the header of a sealed stream.
+fn insert_field(buf: &[u8], field: &[u8]) -> BigBuf {
+    let len = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]) + field.len() as u32;
+
+    let mut res = BigBuf::new();
+    res.try_extend_from_slice(&buf[..5]).unwrap();
+    res.try_extend_from_slice(&len.to_be_bytes()).unwrap();
+    res.try_extend_from_slice(field).unwrap();
+    res.try_extend_from_slice(&buf[9..]).unwrap();
+    res
+}
+
+#[test]
+fn unknown_field() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 1000];
+    rand::thread_rng().fill_bytes(&mut content);
+    let buf = seal(&props, &content);
+
+    // Unknown fields are skipped, but are still authenticated.
+    let inserted = insert_field(&buf, &[0x7F, 0x00, 0x02, 0xAB, 0xCD]);
+    let (dst, valid) = unseal(&props, &inserted);
+
+    assert!(dst.is_empty());
+    assert!(!valid);
+
+    // Unknown critical fields have to be understood.
+    let inserted = insert_field(&buf, &[0xFF, 0x00, 0x02, 0xAB, 0xCD]);
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::IncorrectVersion)
+    ));
+
+    // Fields may not exceed the header.
+    let mut inserted = insert_field(&buf, &[0x7F, 0x00, 0x02, 0xAB, 0xCD]);
+    inserted[8] -= 1;
+    assert!(matches!(
+        OpenerSealed::new(SliceReader::new(&inserted)),
+        Err(Error::FormatViolation)
+    ));
+}
+
+/// Seal according to format version 0, which is no longer written but still has to be opened.
+fn seal_v0(props: &DefaultProps, content: &[u8]) -> BigBuf {
+    use ctr::stream_cipher::{NewStreamCipher, StreamCipher};
+    use hmac::Mac;
+
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk: _ } = props;
+
+    let (c, k) = ibe::kiltz_vahlis_one::encrypt(&pk, &i.derive(), &mut rng);
+    let (aeskey, mackey) = crate::stream::util::derive_keys(k.to_bytes().as_ref());
+    let mut iv = [0u8; IVSIZE];
+    rng.fill_bytes(&mut iv);
+
+    let mut aes = SymCrypt::new(&(*aeskey).into(), &iv.into());
+    let mut hmac = Verifier::new_varkey(&mackey[..]).unwrap();
+
+    let mut buf = BigBuf::new();
+    buf.write(&PRELUDE).unwrap();
+    buf.write(&[FORMAT_VERSION_V0]).unwrap();
+    i.write_to(&mut buf).unwrap();
+    buf.write(&c.to_bytes()).unwrap();
+    buf.write(&iv).unwrap();
+    hmac.input(&buf);
+
+    let mut body = BigBuf::new();
+    body.write(content).unwrap();
+    aes.encrypt(&mut body);
+    hmac.input(&body);
+
+    buf.write(&body).unwrap();
+    buf.write(&hmac.result().code()).unwrap();
+
+    buf
+}
+
+#[test]
+fn open_format_v0() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 1008];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let buf = seal_v0(&props, &content);
+    let (dst, valid) = unseal(&props, &buf);
+
+    assert_eq!(&content.as_ref(), &dst.as_slice());
+    assert!(valid);
+}
+
+#[test]
+fn corrupt_format_v0() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let mut buf = seal_v0(&props, &content);
+    buf[1000] ^= 0x02;
+    let (dst, valid) = unseal(&props, &buf);
+
+    assert_ne!(&content.as_ref(), &dst.as_slice());
+    assert!(!valid);
+}
+
+#[cfg(feature = "std")]
+fn seal_io(props: &DefaultProps, content: &[u8]) -> std::vec::Vec<u8> {
+    use crate::util::IoWriter;
+    use std::io::Write;
+
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+
+    let mut s = Sealer::new(&props.i, &pk, &mut rng, IoWriter::new(vec![])).unwrap();
+    s.write_all(content).unwrap();
+    let (w, len) = s.finish().unwrap();
+    let buf = w.into_inner();
+
+    assert_eq!(len, buf.len() as u64);
+
+    buf
+}
+
+#[cfg(feature = "std")]
+fn unseal_io(props: &DefaultProps, buf: &[u8]) -> (std::vec::Vec<u8>, std::io::Result<usize>) {
+    use crate::util::IoReader;
+    use std::io::Read;
+
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = props;
+
+    let o = OpenerSealed::new(IoReader::new(std::io::Cursor::new(buf))).unwrap();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+    let mut o = o.unseal(i, &UserSecretKey::from(usk)).unwrap();
+
+    let mut dst = vec![];
+    let res = o.read_to_end(&mut dst);
+
+    (dst, res)
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn reflection_io() {
+    let props = DefaultProps::default();
+
+    for len in [0, 1, 511, 512, 1008, 30000].iter() {
+        let mut content = vec![0u8; *len];
+        rand::thread_rng().fill_bytes(&mut content);
+
+        let buf = seal_io(&props, &content);
+        let (dst, res) = unseal_io(&props, &buf);
+
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(content, dst);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn corrupt_io() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let mut buf = seal_io(&props, &content);
+    buf[1000] ^= 0x02;
+    let (dst, res) = unseal_io(&props, &buf);
+
+    assert!(content.starts_with(&dst));
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    let mut buf = seal_v0(&props, &content);
+    buf[1000] ^= 0x02;
+    let (_, res) = unseal_io(&props, &buf);
+
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "std")]
+fn open_seekable(
+    props: &DefaultProps,
+    buf: &[u8],
+) -> Result<SeekableOpener<std::io::Cursor<std::vec::Vec<u8>>>, Error> {
+    use crate::util::IoReader;
+
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = props;
+
+    let o = OpenerSealed::new(IoReader::new(std::io::Cursor::new(buf.to_vec())))?;
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+    o.unseal_seekable(i, &UserSecretKey::from(usk))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn seekable() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let props = DefaultProps::default();
+
+    for len in [0, 1, 511, 512, 1008, 30000].iter() {
+        let mut content = vec![0u8; *len];
+        rand::thread_rng().fill_bytes(&mut content);
+
+        let buf = seal_io(&props, &content);
+        let mut o = open_seekable(&props, &buf).unwrap();
+        assert_eq!(o.len(), content.len() as u64);
+
+        let mut dst = vec![];
+        o.read_to_end(&mut dst).unwrap();
+        assert_eq!(content, dst);
+
+        for (start, n) in [
+            (0, 10),
+            (500, 30),
+            (512, 512),
+            (len / 2, 1000),
+            (len.saturating_sub(1), 2),
+        ]
+        .iter()
+        {
+            let start = core::cmp::min(*start, *len);
+            let end = core::cmp::min(start + n, *len);
+
+            assert_eq!(o.seek(SeekFrom::Start(start as u64)).unwrap(), start as u64);
+            let mut dst = vec![0u8; end - start];
+            o.read_exact(&mut dst).unwrap();
+            assert_eq!(&content[start..end], dst.as_slice());
+        }
+
+        assert_eq!(o.seek(SeekFrom::End(0)).unwrap(), *len as u64);
+        assert_eq!(o.read(&mut [0u8; 16]).unwrap(), 0);
+        assert!(o.seek(SeekFrom::Current(-(*len as i64) - 1)).is_err());
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn seekable_embedded() {
+    use crate::util::IoReader;
+    use std::io::{Read, Seek, SeekFrom};
+
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let mut content = vec![0u8; 30000];
+    rng.fill_bytes(&mut content);
+
+    // The stream is preceded by other data in the reader.
+    let mut buf = vec![0xAAu8; 1000];
+    buf.extend_from_slice(&seal_io(&props, &content));
+    let mut r = std::io::Cursor::new(buf);
+    r.seek(SeekFrom::Start(1000)).unwrap();
+
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+    let o = OpenerSealed::new(IoReader::new(r)).unwrap();
+    let mut o = o
+        .unseal_seekable(&props.i, &UserSecretKey::from(usk))
+        .unwrap();
+    assert_eq!(o.len(), content.len() as u64);
+
+    let mut dst = vec![0u8; 1000];
+    o.seek(SeekFrom::Start(20000)).unwrap();
+    o.read_exact(&mut dst).unwrap();
+    assert_eq!(&content[20000..21000], dst.as_slice());
+
+    let mut dst = vec![];
+    o.seek(SeekFrom::Start(0)).unwrap();
+    o.read_to_end(&mut dst).unwrap();
+    assert_eq!(content, dst);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn corrupt_seekable() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    // Only the range that is covered by the corrupted segment is rejected.
+    let mut buf = seal_io(&props, &content);
+    buf[1000] ^= 0x02;
+    let mut o = open_seekable(&props, &buf).unwrap();
+
+    let mut dst = [0u8; 100];
+    o.seek(SeekFrom::Start(30000)).unwrap();
+    o.read_exact(&mut dst).unwrap();
+    assert_eq!(&content[30000..30100], &dst[..]);
+
+    o.seek(SeekFrom::Start(0)).unwrap();
+    let err = o.read_to_end(&mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Truncating the stream on a segment boundary is detected at its end.
+    let buf = seal_io(&props, &content);
+    let lastlen = (content.len() % BLOCKSIZE) + TAGSIZE;
+    let mut o = open_seekable(&props, &buf[..buf.len() - lastlen]).unwrap();
+
+    o.seek(SeekFrom::End(-10)).unwrap();
+    let err = o.read(&mut dst).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Version 0 streams can not be opened as such.
+    assert!(matches!(
+        open_seekable(&props, &seal_v0(&props, &content)),
+        Err(Error::IncorrectVersion)
+    ));
+}
+
+#[cfg(feature = "std")]
+fn unseal_parallel(
+    props: &DefaultProps,
+    buf: &[u8],
+    threads: usize,
+) -> (std::vec::Vec<u8>, std::io::Result<usize>) {
+    use std::io::Read;
+
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = props;
+
+    let o = OpenerSealed::new(SliceReader::new(buf)).unwrap();
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+    let mut o = o
+        .unseal_parallel(i, &UserSecretKey::from(usk), threads)
+        .unwrap();
+
+    let mut dst = vec![];
+    let res = o.read_to_end(&mut dst);
+
+    (dst, res)
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn parallel_layout() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let props = DefaultProps::default();
+    let pk = PublicKey::from(props.pk);
+    let paddings = [
+        Padding::None,
+        Padding::PowerOfTwo,
+        Padding::Bucket(4096),
+        Padding::Random(8192),
+    ];
+
+    for len in [0, 1, 511, 512, 1024, 200000].iter() {
+        let mut content = vec![0u8; *len];
+        rand::thread_rng().fill_bytes(&mut content);
+
+        for padding in paddings.iter() {
+            let options = SealOptions {
+                padding: *padding,
+                ..Default::default()
+            };
+            let recipients = [props.i.clone()];
+
+            let mut rng = StdRng::seed_from_u64(*len as u64);
+            let mut s = Sealer::with_options(&recipients, &options, &pk, &mut rng, vec![]).unwrap();
+            s.write(&content).unwrap();
+            let (serial, _) = s.finish().unwrap();
+
+            for threads in [1, 4].iter() {
+                let mut rng = StdRng::seed_from_u64(*len as u64);
+                let mut s =
+                    ParallelSealer::new(&recipients, &options, *threads, &pk, &mut rng, vec![])
+                        .unwrap();
+                s.write(&content).unwrap();
+                let (parallel, written) = s.finish().unwrap();
+
+                assert_eq!(written, parallel.len() as u64);
+                assert!(serial == parallel);
+
+                let (dst, res) = unseal_parallel(&props, &parallel, *threads);
+                assert_eq!(res.unwrap(), content.len());
+                assert!(content == dst);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn parallel_batches() {
+    let props = DefaultProps::default();
+    let pk = PublicKey::from(props.pk);
+    let recipients = [props.i.clone()];
+    let options = SealOptions::default();
+
+    // Spans several batches of about 1 MiB per thread.
+    let mut content = vec![0u8; 3 * 1024 * 1024 + 1000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let mut rng = rand::thread_rng();
+    let mut s = ParallelSealer::new(&recipients, &options, 2, &pk, &mut rng, vec![]).unwrap();
+    s.write(&content).unwrap();
+    let (buf, _) = s.finish().unwrap();
+
+    for threads in [1, 2].iter() {
+        let (dst, res) = unseal_parallel(&props, &buf, *threads);
+        assert_eq!(res.unwrap(), content.len());
+        assert!(content == dst);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn corrupt_parallel() {
+    let props = DefaultProps::default();
+
+    let mut content = vec![0u8; 200000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    // Only the plaintext of the segments before the corrupted one is released.
+    let mut buf = seal_io(&props, &content);
+    buf[150000] ^= 0x02;
+    let (dst, res) = unseal_parallel(&props, &buf, 4);
+
+    assert!(dst.len() < 150000);
+    assert!(dst.len() > 100000);
+    assert!(content.starts_with(&dst));
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    // Truncating the stream on a segment boundary is detected.
+    let buf = seal_io(&props, &content);
+    let lastlen = (content.len() % BLOCKSIZE) + TAGSIZE;
+    let (dst, res) = unseal_parallel(&props, &buf[..buf.len() - lastlen], 4);
+
+    assert!(content.starts_with(&dst));
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    // Compressed streams can not be sealed in parallel.
+    #[cfg(feature = "compression")]
+    {
+        let options = SealOptions {
+            compression: Compression::Deflate,
+            ..Default::default()
+        };
+        let pk = PublicKey::from(props.pk);
+        let recipients = [props.i.clone()];
+        let mut rng = rand::thread_rng();
+        assert!(matches!(
+            ParallelSealer::new(&recipients, &options, 4, &pk, &mut rng, vec![]),
+            Err(Error::ConstraintViolation)
+        ));
+    }
+}
+
+#[cfg(feature = "std")]
+fn seal_block_size<const B: usize>(
+    props: &DefaultProps,
+    padding: Padding,
+    content: &[u8],
+) -> std::vec::Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+    let options = SealOptions {
+        padding,
+        ..Default::default()
+    };
+
+    let mut s = Sealer::<_, B>::with_block_size(
+        core::slice::from_ref(&props.i),
+        &options,
+        &pk,
+        &mut rng,
+        vec![],
+    )
+    .unwrap();
+    s.write(content).unwrap();
+    s.finish().unwrap().0
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn block_size() {
+    use std::io::Read;
+
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = &props;
+    let usk = UserSecretKey::from(ibe::kiltz_vahlis_one::extract_usk(
+        pk,
+        sk,
+        &i.derive(),
+        &mut rng,
+    ));
+
+    let mut content = vec![0u8; 20000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    // A larger block size is recorded in the header, and is picked up by the openers.
+    let buf = seal_block_size::<4096>(&props, Padding::None, &content);
+    // Only 5 segments are sealed instead of 40, whereas the header holds a single extra field.
+    assert_eq!(
+        seal_io(&props, &content).len() - buf.len(),
+        35 * TAGSIZE - 7
+    );
+
+    let o = OpenerSealed::<_, 4096>::with_block_size(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.block_size(), 4096);
+    let mut o = o.unseal(i, &usk).unwrap();
+    let mut dst = vec![];
+    o.write_to(&mut dst).unwrap();
+    assert!(o.validate());
+    assert_eq!(content, dst);
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.block_size(), 4096);
+    let (dst, res) = unseal_io(&props, &buf);
+    assert_eq!(res.unwrap(), content.len());
+    assert_eq!(content, dst);
+
+    // The parallel and seekable openers pick up any block size as well.
+    let (dst, res) = unseal_parallel(&props, &buf, 4);
+    assert_eq!(res.unwrap(), content.len());
+    assert_eq!(content, dst);
+
+    let mut o = open_seekable(&props, &buf).unwrap();
+    let mut dst = vec![];
+    o.read_to_end(&mut dst).unwrap();
+    assert_eq!(content, dst);
+
+    // A smaller block size fits in the buffer of the default opener.
+    for padding in [Padding::None, Padding::PowerOfTwo].iter() {
+        let buf = seal_block_size::<64>(&props, *padding, &content);
+        let (dst, res) = unseal_io(&props, &buf);
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(content, dst);
+    }
+
+    // Block sizes that can not hold a padding trailer are rejected.
+    let pk = PublicKey::from(*pk);
+    let options = SealOptions::default();
+    assert!(matches!(
+        Sealer::<_, 4>::with_block_size(core::slice::from_ref(i), &options, &pk, &mut rng, vec![]),
+        Err(Error::ConstraintViolation)
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn large_block_size() {
+    let props = DefaultProps::default();
+
+    let mut content = vec![0u8; 200000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let buf = seal_block_size::<65536>(&props, Padding::None, &content);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.block_size(), 65536);
+
+    let (dst, res) = unseal_io(&props, &buf);
+    assert_eq!(res.unwrap(), content.len());
+    assert_eq!(content, dst);
+
+    #[cfg(feature = "async")]
+    {
+        let (dst, res) = unseal_async(&props, &buf);
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(content, dst);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[test]
+fn block_size_exceeds_buffer() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = &props;
+    let usk = UserSecretKey::from(ibe::kiltz_vahlis_one::extract_usk(
+        pk,
+        sk,
+        &i.derive(),
+        &mut rng,
+    ));
+
+    let options = SealOptions::default();
+    let mut buf = BigBuf::new();
+    let mut s = Sealer::<_, 1024>::with_block_size(
+        core::slice::from_ref(i),
+        &options,
+        &PublicKey::from(*pk),
+        &mut rng,
+        &mut buf,
+    )
+    .unwrap();
+    s.write(&[0u8; 3000]).unwrap();
+    s.finish().unwrap();
+
+    // Without `std`, the block size has to fit in the buffer of the opener.
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert_eq!(o.block_size(), 1024);
+    assert!(matches!(o.unseal(i, &usk), Err(Error::ConstraintViolation)));
+
+    let o = OpenerSealed::<_, 1024>::with_block_size(SliceReader::new(&buf)).unwrap();
+    let mut o = o.unseal(i, &usk).unwrap();
+    let mut dst = BigBuf::new();
+    o.write_to(&mut dst).unwrap();
+    assert!(o.validate());
+    assert_eq!(&[0u8; 3000][..], dst.as_slice());
+}
+
+#[cfg(feature = "async")]
+fn seal_async(props: &DefaultProps, content: &[u8]) -> std::vec::Vec<u8> {
+    use futures::io::AsyncWriteExt;
+
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+
+    let mut s = AsyncSealer::new(&props.i, &pk, &mut rng, vec![]).unwrap();
+
+    futures::executor::block_on(async {
+        for chunk in content.chunks(1000) {
+            s.write_all(chunk).await.unwrap();
+        }
+
+        let (buf, len) = s.finish().await.unwrap();
+        assert_eq!(len, buf.len() as u64);
+
+        buf
+    })
+}
+
+#[cfg(feature = "async")]
+fn unseal_async(props: &DefaultProps, buf: &[u8]) -> (std::vec::Vec<u8>, std::io::Result<usize>) {
+    use futures::io::AsyncReadExt;
+
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = props;
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+
+    futures::executor::block_on(async {
+        let o = AsyncOpenerSealed::new(futures::io::Cursor::new(buf))
+            .await
+            .unwrap();
+        let mut o = o.unseal(i, &UserSecretKey::from(usk)).await.unwrap();
+
+        let mut dst = vec![];
+        let res = o.read_to_end(&mut dst).await;
+
+        (dst, res)
+    })
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn reflection_async() {
+    let props = DefaultProps::default();
+
+    for len in [0, 1, 511, 512, 1008, 30000].iter() {
+        let mut content = vec![0u8; *len];
+        rand::thread_rng().fill_bytes(&mut content);
+
+        let buf = seal_async(&props, &content);
+        let (dst, res) = unseal_async(&props, &buf);
+
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(content, dst);
+
+        let (dst, valid) = unseal(&props, &buf);
+        assert_eq!(content.as_slice(), dst.as_slice());
+        assert!(valid);
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn open_async() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    for buf in [seal(&props, &content), seal_v0(&props, &content)].iter() {
+        let (dst, res) = unseal_async(&props, &buf);
+
+        assert_eq!(res.unwrap(), content.len());
+        assert_eq!(&content[..], dst.as_slice());
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn corrupt_async() {
+    let props = DefaultProps::default();
+
+    let mut content = [0u8; 60000];
+    rand::thread_rng().fill_bytes(&mut content);
+
+    let mut buf = seal_async(&props, &content);
+    buf[1000] ^= 0x02;
+    let (dst, res) = unseal_async(&props, &buf);
+
+    assert!(content.starts_with(&dst));
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    let buf = seal_async(&props, &content);
+    let (_, res) = unseal_async(&props, &buf[..buf.len() - 1]);
+
+    assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "signing")]
+fn seal_signed(props: &DefaultProps, options: &SealOptions, content: &[u8]) -> BigBuf {
+    let mut rng = rand::thread_rng();
+    let pk = PublicKey::from(props.pk);
+
+    let mut s = Sealer::with_options(
+        core::slice::from_ref(&props.i),
+        options,
+        &pk,
+        &mut rng,
+        BigBuf::new(),
+    )
+    .unwrap();
+    s.write(content).unwrap();
+    let (buf, len) = s.finish().unwrap();
+    assert_eq!(len, buf.len() as u64);
+
+    buf
+}
+
+#[cfg(feature = "signing")]
+fn unseal_signed(
+    props: &DefaultProps,
+    mvk: &MasterVerifyingKey,
+    buf: &[u8],
+) -> (BigBuf, bool, Option<Identity>) {
+    let mut rng = rand::thread_rng();
+    let DefaultProps { i, pk, sk } = props;
+    let usk = ibe::kiltz_vahlis_one::extract_usk(pk, sk, &i.derive(), &mut rng);
+
+    let o = OpenerSealed::new(SliceReader::new(buf)).unwrap();
+    let mut o = o.unseal(i, &UserSecretKey::from(usk)).unwrap();
+    assert!(o.sender(mvk).is_none());
+
+    let mut dst = BigBuf::new();
+    let res = o.write_to(&mut dst);
+    let sender = o.sender(mvk).cloned();
+
+    (dst, res.is_ok() && o.validate(), sender)
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn signed() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let (mvk, msk) = MasterSigningKey::setup(&mut rng);
+    let sender = Identity::new(
+        1566722350,
+        "pbdf.pbdf.email.email",
+        Some("sender@example.com"),
+    )
+    .unwrap();
+    let key = msk.issue(&sender, &mut rng).unwrap();
+    assert_eq!(key.certificate().identity(), &sender);
+    assert!(key.certificate().is_issued_by(&mvk));
+
+    // The sealer borrows the key from the options, which are reused for every stream.
+    let mut options = SealOptions {
+        sender: Some(key),
+        ..Default::default()
+    };
+
+    for len in [0, 1, 511, 512, 1008, 30000].iter() {
+        let mut content = [0u8; 30000];
+        let content = &mut content[..*len];
+        rng.fill_bytes(content);
+
+        for padding in [Padding::None, Padding::PowerOfTwo].iter() {
+            options.padding = *padding;
+            let buf = seal_signed(&props, &options, content);
+            let (dst, valid, signer) = unseal_signed(&props, &mvk, &buf);
+
+            assert_eq!(&*content, dst.as_slice());
+            assert!(valid);
+            assert_eq!(signer.as_ref(), Some(&sender));
+        }
+    }
+
+    // The sender is only known once the entire stream is read, and is queried before `validate`
+    // consumes the opener.
+    options.padding = Padding::None;
+    let buf = seal_signed(&props, &options, &[0u8; 3000]);
+    let usk = ibe::kiltz_vahlis_one::extract_usk(&props.pk, &props.sk, &props.i.derive(), &mut rng);
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let mut o = o.unseal(&props.i, &UserSecretKey::from(usk)).unwrap();
+    o.read().unwrap();
+    assert!(o.sender(&mvk).is_none());
+    while o.read().is_ok() {}
+    assert_eq!(o.sender(&mvk), Some(&sender));
+    assert!(o.validate());
+
+    // The sender is only exposed if the certificate was issued by the PKG with the verifying key.
+    let (other, _) = MasterSigningKey::setup(&mut rng);
+    let buf = seal_signed(&props, &options, &[0u8; 1000]);
+    assert!(OpenerSealed::new(SliceReader::new(&buf))
+        .unwrap()
+        .is_signed());
+    let (_, valid, signer) = unseal_signed(&props, &other, &buf);
+    assert!(valid);
+    assert!(signer.is_none());
+
+    // Unsigned streams never expose a sender.
+    let buf = seal(&props, &[0u8; 1000]);
+    assert!(!OpenerSealed::new(SliceReader::new(&buf))
+        .unwrap()
+        .is_signed());
+    let (_, valid, signer) = unseal_signed(&props, &mvk, &buf);
+    assert!(valid);
+    assert!(signer.is_none());
+
+    // The master signing key survives a round trip through its byte representation.
+    let msk = MasterSigningKey::from_bytes(&msk.to_bytes()).unwrap();
+    assert_eq!(msk.verifying_key(), mvk);
+    assert_eq!(MasterVerifyingKey::from_bytes(&mvk.to_bytes()), Some(mvk));
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn corrupt_signed() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let (mvk, msk) = MasterSigningKey::setup(&mut rng);
+    let sender = Identity::new(
+        1566722350,
+        "pbdf.pbdf.email.email",
+        Some("sender@example.com"),
+    )
+    .unwrap();
+    let options = SealOptions {
+        sender: Some(msk.issue(&sender, &mut rng).unwrap()),
+        ..Default::default()
+    };
+
+    let mut content = [0u8; 3000];
+    rng.fill_bytes(&mut content);
+    let buf = seal_signed(&props, &options, &content);
+
+    // Corrupting the signature withholds the final segment.
+    let mut corrupted = buf.clone();
+    let n = corrupted.len();
+    corrupted[n - 1] ^= 0x01;
+    let (dst, valid, signer) = unseal_signed(&props, &mvk, &corrupted);
+    assert!(!valid);
+    assert!(signer.is_none());
+    assert!(dst.len() < content.len());
+    assert!(content.starts_with(&dst));
+
+    // As does truncating or stripping the signature.
+    for strip in [1, SIGNATURE_SIZE, SIGNATURE_SIZE + 1].iter() {
+        let (_, valid, signer) = unseal_signed(&props, &mvk, &buf[..buf.len() - strip]);
+        assert!(!valid);
+        assert!(signer.is_none());
+    }
+
+    // Appending bytes shifts the signature into the final segment.
+    let mut extended = buf.clone();
+    extended.push(0);
+    let (_, valid, signer) = unseal_signed(&props, &mvk, &extended);
+    assert!(!valid);
+    assert!(signer.is_none());
+}
+
+#[cfg(all(feature = "std", feature = "signing"))]
+#[test]
+fn signed_parallel() {
+    let props = DefaultProps::default();
+    let mut rng = rand::thread_rng();
+
+    let (mvk, msk) = MasterSigningKey::setup(&mut rng);
+    let key = msk.issue(&props.i, &mut rng).unwrap();
+
+    let mut content = vec![0u8; 200000];
+    rng.fill_bytes(&mut content);
+
+    let options = SealOptions {
+        sender: Some(key),
+        ..Default::default()
+    };
+    let pk = PublicKey::from(props.pk);
+    let recipients = [props.i.clone()];
+
+    let mut s = ParallelSealer::new(&recipients, &options, 4, &pk, &mut rng, vec![]).unwrap();
+    s.write(&content).unwrap();
+    let (buf, _) = s.finish().unwrap();
+
+    // Signed streams can only be opened serially.
+    let usk = || {
+        UserSecretKey::from(ibe::kiltz_vahlis_one::extract_usk(
+            &props.pk,
+            &props.sk,
+            &props.i.derive(),
+            &mut rand::thread_rng(),
+        ))
+    };
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    assert!(matches!(
+        o.unseal_parallel(&props.i, &usk(), 4),
+        Err(Error::ConstraintViolation)
+    ));
+
+    let o = OpenerSealed::new(SliceReader::new(&buf)).unwrap();
+    let mut o = o.unseal(&props.i, &usk()).unwrap();
+    let mut dst = vec![];
+    o.write_to(&mut dst).unwrap();
+    assert_eq!(o.sender(&mvk), Some(&props.i));
+    assert!(o.validate());
+    assert!(content == dst);
+}
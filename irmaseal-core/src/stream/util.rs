@@ -1,32 +1,145 @@
+use arrayref::array_ref;
 use arrayvec::{Array, ArrayVec};
 use digest::{Digest, FixedOutput};
-use ibe::kiltz_vahlis_one::SymmetricKey;
 use rand::{CryptoRng, Rng};
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::stream::*;
 use crate::*;
 
-pub(crate) fn derive_keys(key: &SymmetricKey) -> ([u8; KEYSIZE], [u8; KEYSIZE]) {
+/// A symmetric key, which is overwritten with zeroes when dropped.
+pub(crate) type Key = Zeroizing<[u8; KEYSIZE]>;
+
+/// Derive the keys of format version 0 streams.
+pub(crate) fn derive_keys(key: &[u8]) -> (Key, Key) {
     let mut h = sha3::Sha3_512::new();
-    h.input(key.to_bytes().as_ref());
-    let buf = h.fixed_result();
+    h.input(key);
+    let mut buf = h.fixed_result();
 
-    let mut aeskey = [0u8; KEYSIZE];
-    let mut mackey = [0u8; KEYSIZE];
+    let mut aeskey = Key::new([0u8; KEYSIZE]);
+    let mut mackey = Key::new([0u8; KEYSIZE]);
 
     let (a, b) = buf.as_slice().split_at(KEYSIZE);
     aeskey.copy_from_slice(&a);
     mackey.copy_from_slice(&b);
+    buf.as_mut_slice().zeroize();
 
     (aeskey, mackey)
 }
 
-pub(crate) fn generate_iv<R: Rng + CryptoRng>(r: &mut R) -> [u8; IVSIZE] {
-    let mut res = [0u8; IVSIZE];
+/// The key schedule of format version 2 streams, which derives all keys from the body key using
+/// HKDF-SHA3-256. The format version is used as salt, and every key is expanded under its own label.
+pub(crate) struct KeySchedule(hkdf::Hkdf<sha3::Sha3_256>);
+
+impl KeySchedule {
+    pub fn new(key: &[u8; KEYSIZE]) -> KeySchedule {
+        let mut salt = [0u8; PRELUDE.len() + 1];
+        salt[..PRELUDE.len()].copy_from_slice(&PRELUDE);
+        salt[PRELUDE.len()] = FORMAT_VERSION_V2;
+
+        KeySchedule(hkdf::Hkdf::new(Some(&salt), key))
+    }
+
+    fn expand(&self, label: &[u8], context: &[u8]) -> Key {
+        let mut info = ArrayVec::<[u8; 64]>::new();
+        info.try_extend_from_slice(label).unwrap();
+        info.try_extend_from_slice(context).unwrap();
+
+        let mut res = Key::new([0u8; KEYSIZE]);
+        // The output is far shorter than the maximum length of HKDF.
+        self.0.expand(&info, res.as_mut()).unwrap();
+        res
+    }
+
+    /// The key with which the metadata is sealed, as part of the header.
+    pub fn metadata_key(&self) -> Key {
+        self.expand(b"IRMAseal metadata key", &[])
+    }
+
+    /// The key with which the segments are sealed, which is bound to the digest of the header.
+    pub fn segment_key(&self, headerdigest: &[u8; DIGESTSIZE]) -> Key {
+        self.expand(b"IRMAseal segment key", headerdigest)
+    }
+
+    /// A commitment to the body key, as recorded in the header.
+    ///
+    /// Ensures that a stream can not be opened validly under two different body keys,
+    /// which AES-GCM does not guarantee by itself.
+    pub fn commitment(&self) -> [u8; KEYSIZE] {
+        *self.expand(b"IRMAseal key commitment", &[])
+    }
+}
+
+/// Mask a body key with a key derived from the KEM shared secret of a single recipient.
+///
+/// Applying the same mask twice yields the original body key.
+pub(crate) fn mask_key(ss: &[u8], key: &[u8; KEYSIZE]) -> Key {
+    let mut h = sha3::Sha3_256::new();
+    h.input(b"IRMAseal key wrap");
+    h.input(ss);
+    let mut mask = h.fixed_result();
+
+    let mut res = Key::new([0u8; KEYSIZE]);
+    for (r, (a, b)) in res.iter_mut().zip(key.iter().zip(mask.iter())) {
+        *r = a ^ b;
+    }
+    mask.as_mut_slice().zeroize();
+
+    res
+}
+
+/// A short hint of the identity of a hidden recipient, by which the recipient can recognise the stream.
+///
+/// Is salted with the KEM ciphertext, such that hints can not be linked across streams.
+/// The hint is kept short on purpose, as it only needs to tell apart the few recipients of a
+/// single stream, and should not confirm a guessed attribute value.
+pub(crate) fn recipient_hint(identity: &Identity, ciphertext: &[u8]) -> [u8; HINTSIZE] {
+    let mut h = sha3::Sha3_256::new();
+    h.input(b"IRMAseal recipient hint");
+    h.input(ciphertext);
+
+    let mut dw = DigestWriter::new(h, Sink);
+    // Writing to a digest can not fail.
+    identity.write_to(&mut dw).unwrap();
+    let digest = dw.disclose().0.fixed_result();
+
+    *array_ref![digest, 0, HINTSIZE]
+}
+
+pub(crate) fn generate_key<R: Rng + CryptoRng>(r: &mut R) -> Key {
+    let mut res = Key::new([0u8; KEYSIZE]);
+    r.fill_bytes(res.as_mut());
+    res
+}
+
+pub(crate) fn generate_nonce_prefix<R: Rng + CryptoRng>(r: &mut R) -> [u8; NONCE_PREFIX_SIZE] {
+    let mut res = [0u8; NONCE_PREFIX_SIZE];
     r.fill_bytes(&mut res);
     res
 }
 
+/// Keep reading until `buf` is full or the stream is exhausted, yielding the number of bytes read.
+pub(crate) fn read_fill<R: Readable>(r: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+
+    while len < buf.len() {
+        let src = match r.read_bytes(buf.len() - len) {
+            Ok(src) => src,
+            Err(Error::EndOfStream) => break,
+            Err(e) => return Err(e),
+        };
+
+        if src.is_empty() {
+            break;
+        }
+
+        buf[len..len + src.len()].copy_from_slice(src);
+        len += src.len();
+    }
+
+    Ok(len)
+}
+
 /// Nested Reader that archives all bytes passing through in buf.
 pub(crate) struct ArchiveReader<R: Readable, A: Array> {
     buf: ArrayVec<A>,
@@ -59,3 +172,151 @@ impl<R: Readable, A: Array<Item = u8>> Readable for ArchiveReader<R, A> {
         Ok(res)
     }
 }
+
+/// Nested Reader that digests all bytes passing through.
+pub(crate) struct DigestReader<R: Readable, D: Digest> {
+    d: D,
+    r: R,
+}
+
+impl<R: Readable, D: Digest> DigestReader<R, D> {
+    /// Continue digesting from an existing digest state.
+    pub fn new(d: D, r: R) -> DigestReader<R, D> {
+        DigestReader { d, r }
+    }
+
+    pub fn disclose(self) -> (D, R) {
+        (self.d, self.r)
+    }
+}
+
+impl<R: Readable, D: Digest> Readable for DigestReader<R, D> {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let res = self.r.read_byte()?;
+        self.d.input([res]);
+        Ok(res)
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        let res = self.r.read_bytes(n)?;
+        self.d.input(res);
+        Ok(res)
+    }
+}
+
+/// Nested Reader that only allows reading up to a fixed number of bytes.
+///
+/// Reading beyond the limit is a FormatViolation, as it is determined by the stream itself.
+pub(crate) struct LimitedReader<R: Readable> {
+    r: R,
+    remaining: usize,
+}
+
+impl<R: Readable> LimitedReader<R> {
+    pub fn new(r: R, limit: usize) -> LimitedReader<R> {
+        LimitedReader {
+            r,
+            remaining: limit,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Read and discard the remaining bytes.
+    pub fn skip(&mut self) -> Result<(), Error> {
+        while self.remaining > 0 {
+            self.read_bytes_strict(core::cmp::min(self.remaining, BLOCKSIZE))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn disclose(self) -> R {
+        self.r
+    }
+}
+
+impl<R: Readable> Readable for LimitedReader<R> {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        if self.remaining == 0 {
+            return Err(Error::FormatViolation);
+        }
+
+        let res = self.r.read_byte()?;
+        self.remaining -= 1;
+        Ok(res)
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        if n > self.remaining {
+            return Err(Error::FormatViolation);
+        }
+
+        let res = self.r.read_bytes(n)?;
+        self.remaining -= res.len();
+        Ok(res)
+    }
+}
+
+/// Nested Writer that digests all bytes passing through.
+pub(crate) struct DigestWriter<W: Writable, D: Digest> {
+    d: D,
+    w: W,
+}
+
+impl<W: Writable, D: Digest> DigestWriter<W, D> {
+    pub fn new(d: D, w: W) -> DigestWriter<W, D> {
+        DigestWriter { d, w }
+    }
+
+    pub fn disclose(self) -> (D, W) {
+        (self.d, self.w)
+    }
+}
+
+impl<W: Writable, D: Digest> Writable for DigestWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.d.input(buf);
+        self.w.write(buf)
+    }
+}
+
+/// Writer that discards all bytes, to determine the length of a serialization by means of a `CountingWriter`.
+pub(crate) struct Sink;
+
+impl Writable for Sink {
+    fn write(&mut self, _buf: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Nested Writer that counts all bytes passing through.
+pub(crate) struct CountingWriter<W: Writable> {
+    w: W,
+    count: u64,
+}
+
+impl<W: Writable> CountingWriter<W> {
+    pub fn new(w: W) -> CountingWriter<W> {
+        CountingWriter { w, count: 0 }
+    }
+
+    pub fn disclose(self) -> (W, u64) {
+        (self.w, self.count)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.w
+    }
+}
+
+impl<W: Writable> Writable for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.w.write(buf)?;
+        self.count += buf.len() as u64;
+        Ok(())
+    }
+}
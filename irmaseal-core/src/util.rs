@@ -10,11 +10,16 @@ impl<'a, T> SliceReader<'a, T> {
     pub fn new(buf: &'a [T]) -> SliceReader<'a, T> {
         SliceReader { buf, i: 0 }
     }
+
+    /// The number of elements that have not been read yet.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.i
+    }
 }
 
 impl<'a> Readable for SliceReader<'a, u8> {
     fn read_byte(&mut self) -> Result<u8, Error> {
-        if self.buf.len() < self.i {
+        if self.buf.len() <= self.i {
             return Err(Error::EndOfStream);
         }
 
@@ -37,7 +42,7 @@ impl<'a> Readable for SliceReader<'a, u8> {
         }
 
         let res = &self.buf[self.i..end];
-        self.i += n;
+        self.i = end;
 
         Ok(res)
     }
@@ -45,19 +50,90 @@ impl<'a> Readable for SliceReader<'a, u8> {
 
 impl<A: Array<Item = u8>> Writable for ArrayVec<A> {
     fn write(&mut self, data: &[u8]) -> Result<(), Error> {
-        unsafe {
-            let len = self.len();
+        self.try_extend_from_slice(data)
+            .or(Err(Error::UpstreamWritableError))
+    }
+}
 
-            if len + data.len() > A::CAPACITY {
-                return Err(Error::UpstreamWritableError);
-            }
+#[cfg(feature = "std")]
+impl Writable for std::vec::Vec<u8> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
 
-            let tail = core::slice::from_raw_parts_mut(self.get_unchecked_mut(len), data.len());
-            tail.copy_from_slice(data);
+/// Adapter that turns any `std::io::Read` into a `Readable`.
+#[cfg(feature = "std")]
+pub struct IoReader<R: std::io::Read> {
+    r: R,
+    buf: std::vec::Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> IoReader<R> {
+    pub fn new(r: R) -> IoReader<R> {
+        IoReader { r, buf: vec![] }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
 
-            self.set_len(len + data.len());
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Readable for IoReader<R> {
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes_strict(1)?[0])
+    }
+
+    /// Will only yield less than `n` bytes if the underlying reader is exhausted.
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], Error> {
+        use std::io::ErrorKind;
+
+        if self.buf.len() < n {
+            self.buf.resize(n, 0u8);
         }
-        Ok(())
+
+        let mut len = 0;
+        while len < n {
+            match self.r.read(&mut self.buf[len..n]) {
+                Ok(0) => break,
+                Ok(l) => len += l,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+
+        if len == 0 && n > 0 {
+            return Err(Error::EndOfStream);
+        }
+
+        Ok(&self.buf[0..len])
+    }
+}
+
+/// Adapter that turns any `std::io::Write` into a `Writable`.
+#[cfg(feature = "std")]
+pub struct IoWriter<W: std::io::Write> {
+    w: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoWriter<W> {
+    pub fn new(w: W) -> IoWriter<W> {
+        IoWriter { w }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Writable for IoWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.w.write_all(buf).map_err(Error::Io)
     }
 }
 
@@ -5,14 +5,19 @@ use std::fmt::{Display, Formatter};
 /// Show the error as an HTTP response for Actix-web.
 impl ResponseError for Error {
     fn render_response(&self) -> HttpResponse {
-        let body = json!({
+        let mut body = json!({
             "error": true,
             "message": format!("{}", self),
         });
 
+        // Tell the client when it can retry to fetch the key.
+        if let Error::ChronologyError(available) = self {
+            body["available"] = json!(available);
+        }
+
         let mut response = match self {
             Error::Core(_) => HttpResponse::InternalServerError(),
-            Error::ChronologyError => HttpResponse::BadRequest(),
+            Error::ChronologyError(_) => HttpResponse::BadRequest(),
             Error::SessionNotFound => HttpResponse::NotFound(),
             Error::UpstreamError => HttpResponse::ServiceUnavailable(),
             Error::Unexpected => HttpResponse::InternalServerError(),
@@ -25,7 +30,8 @@ impl ResponseError for Error {
 #[derive(Debug)]
 pub enum Error {
     Core(irmaseal_core::Error),
-    ChronologyError,
+    /// The key is requested before its timestamp, which is when it becomes available.
+    ChronologyError(u64),
     SessionNotFound,
     UpstreamError,
     Unexpected,
@@ -33,16 +39,16 @@ pub enum Error {
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Error::Core(_) => "core",
-                Error::ChronologyError => "chronology error",
-                Error::SessionNotFound => "session not found",
-                Error::UpstreamError => "upstream error",
-                Error::Unexpected => "unexpected",
-            }
-        )
+        match self {
+            Error::Core(_) => write!(f, "core"),
+            Error::ChronologyError(available) => write!(
+                f,
+                "chronology error, the key is available from {}",
+                available
+            ),
+            Error::SessionNotFound => write!(f, "session not found"),
+            Error::UpstreamError => write!(f, "upstream error"),
+            Error::Unexpected => write!(f, "unexpected"),
+        }
     }
 }
@@ -1,5 +1,9 @@
 use clap::ArgMatches;
-use ibe::kiltz_vahlis_one::setup;
+use irmaseal_core::stream::MasterSigningKey;
+use irmaseal_core::SecretKey;
+use zeroize::Zeroizing;
+
+use crate::server::PkgKem;
 
 use std::fs::OpenOptions;
 use std::os::unix::fs::OpenOptionsExt;
@@ -22,13 +26,30 @@ fn write_owned<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) {
 
 pub fn exec(m: &ArgMatches) {
     let mut rng = rand::thread_rng();
-    let (pk, sk) = setup(&mut rng);
+    let (pk, sk) = SecretKey::<PkgKem>::setup(&mut rng);
 
     let public = m.value_of("public").unwrap();
     let secret = m.value_of("secret").unwrap();
 
     write_owned(public, pk.to_bytes().as_ref());
-    write_owned(secret, sk.to_bytes().as_ref());
+    let sk_bytes = Zeroizing::new(sk.to_bytes());
+    write_owned(secret, &sk_bytes[..]);
 
     println!("Written {} and {}", public, secret);
+
+    // A PKG that does not issue signing keys should not hold a second master secret.
+    if !m.is_present("signing") {
+        return;
+    }
+
+    let (mvk, msk) = MasterSigningKey::setup(&mut rng);
+
+    let signing_public = m.value_of("signing-public").unwrap();
+    let signing_secret = m.value_of("signing-secret").unwrap();
+
+    write_owned(signing_public, mvk.to_bytes());
+    let msk_bytes = Zeroizing::new(msk.to_bytes());
+    write_owned(signing_secret, &msk_bytes[..]);
+
+    println!("Written {} and {}", signing_public, signing_secret);
 }
@@ -1,7 +1,9 @@
 mod parameters;
 mod request;
 mod request_fetch;
+mod sign_fetch;
 
 pub use parameters::*;
 pub use request::*;
 pub use request_fetch::*;
+pub use sign_fetch::*;
@@ -1,13 +1,17 @@
-use crate::server::AppState;
+use crate::server::{AppState, PkgKem};
 use actix_web::web::{Data, HttpResponse};
 use futures::future::{ok, Future};
 use irmaseal_core::api::Parameters;
+use irmaseal_core::kem::Kem;
+use irmaseal_core::stream::FORMAT_VERSION;
 
 pub fn parameters(state: Data<AppState>) -> impl Future<Item = HttpResponse, Error = crate::Error> {
     let parameters = Parameters {
-        format_version: 0x00,
+        format_version: FORMAT_VERSION,
         max_age: 300,
+        scheme: PkgKem::SCHEME,
         public_key: state.pk.into(),
+        verifying_key: state.signing_sk.as_ref().map(|sk| sk.verifying_key()),
     };
 
     ok(HttpResponse::Ok().json(parameters))
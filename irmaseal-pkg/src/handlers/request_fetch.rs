@@ -1,7 +1,7 @@
 use actix_web::web::{Data, HttpResponse, Path};
 use futures::future::{ok, Future};
 use irmaseal_core::api::{KeyResponse, KeyStatus};
-use irmaseal_core::Identity;
+use irmaseal_core::{Identity, PublicKey};
 
 use irma::client::Client;
 use irma::session::*;
@@ -10,7 +10,7 @@ use crate::server::AppState;
 use crate::Error;
 
 /// Fetch identity iff valid, or else yield nothing.
-fn fetch_identity(
+pub(super) fn fetch_identity(
     timestamp: u64,
     disclosed: &Option<Vec<Vec<DisclosedAttribute>>>,
 ) -> Option<Identity> {
@@ -30,18 +30,14 @@ fn fetch_identity(
     Identity::new(timestamp, &disclosed.id, Some(&v)).ok()
 }
 
-pub fn request_fetch(
-    state: Data<AppState>,
-    path: Path<(String, u64)>,
-) -> impl Future<Item = HttpResponse, Error = crate::Error> {
-    let (token, timestamp) = path.into_inner();
-
-    let AppState {
-        pk,
-        sk,
-        irma_server_host,
-    } = state.get_ref().clone();
-
+/// Fetch the result of the IRMA session, iff the timestamp of the requested key has passed.
+///
+/// Keys for a future timestamp are withheld until then, which makes time-release possible.
+pub(super) fn fetch_result(
+    irma_server_host: String,
+    token: String,
+    timestamp: u64,
+) -> impl Future<Item = SessionResult, Error = crate::Error> {
     ok(())
         .and_then(move |_| {
             let now = std::time::SystemTime::now()
@@ -49,7 +45,7 @@ pub fn request_fetch(
                 .unwrap()
                 .as_secs();
             if timestamp > now {
-                Err(Error::ChronologyError)
+                Err(Error::ChronologyError(timestamp))
             } else {
                 Ok(())
             }
@@ -63,29 +59,43 @@ pub fn request_fetch(
                     _ => crate::Error::UpstreamError,
                 })
         })
-        .and_then(move |r: SessionResult| {
-            let d = |status: KeyStatus| KeyResponse { status, key: None };
+}
 
-            let result = match r.status {
-                SessionStatus::Initialized => d(KeyStatus::Initialized),
-                SessionStatus::Connected => d(KeyStatus::Connected),
-                SessionStatus::Cancelled => d(KeyStatus::Cancelled),
-                SessionStatus::Timeout => d(KeyStatus::Timeout),
-                SessionStatus::Done => match fetch_identity(timestamp, &r.disclosed) {
-                    Some(i) => {
-                        let mut rng = rand::thread_rng();
-                        let usk =
-                            ibe::kiltz_vahlis_one::extract_usk(&pk, &sk, &i.derive(), &mut rng);
+pub fn request_fetch(
+    state: Data<AppState>,
+    path: Path<(String, u64)>,
+) -> impl Future<Item = HttpResponse, Error = crate::Error> {
+    let (token, timestamp) = path.into_inner();
+
+    let AppState {
+        pk,
+        sk,
+        irma_server_host,
+        ..
+    } = state.get_ref().clone();
 
-                        KeyResponse {
-                            status: KeyStatus::DoneValid,
-                            key: Some(usk.into()),
-                        }
+    fetch_result(irma_server_host, token, timestamp).and_then(move |r: SessionResult| {
+        let d = |status: KeyStatus| KeyResponse { status, key: None };
+
+        let result = match r.status {
+            SessionStatus::Initialized => d(KeyStatus::Initialized),
+            SessionStatus::Connected => d(KeyStatus::Connected),
+            SessionStatus::Cancelled => d(KeyStatus::Cancelled),
+            SessionStatus::Timeout => d(KeyStatus::Timeout),
+            SessionStatus::Done => match fetch_identity(timestamp, &r.disclosed) {
+                Some(i) => {
+                    let mut rng = rand::thread_rng();
+                    let usk = sk.extract_usk(&PublicKey::from(pk), &i, &mut rng);
+
+                    KeyResponse {
+                        status: KeyStatus::DoneValid,
+                        key: Some(usk),
                     }
-                    None => d(KeyStatus::DoneInvalid),
-                },
-            };
+                }
+                None => d(KeyStatus::DoneInvalid),
+            },
+        };
 
-            Ok(HttpResponse::Ok().json(result))
-        })
+        Ok(HttpResponse::Ok().json(result))
+    })
 }
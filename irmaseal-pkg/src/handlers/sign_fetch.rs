@@ -0,0 +1,54 @@
+use actix_web::web::{Data, HttpResponse, Path};
+use futures::future::Future;
+use irmaseal_core::api::{KeyStatus, SigningKeyResponse};
+
+use irma::session::*;
+
+use crate::handlers::request_fetch::{fetch_identity, fetch_result};
+use crate::server::AppState;
+
+/// Issue a signing key for the identity disclosed in the IRMA session.
+///
+/// The signing key is certified with the master signing key of the PKG,
+/// such that the recipients of a signed stream can verify the identity of its sender.
+/// Is only routed when the PKG has a master signing key.
+pub fn sign_fetch(
+    state: Data<AppState>,
+    path: Path<(String, u64)>,
+) -> impl Future<Item = HttpResponse, Error = crate::Error> {
+    let (token, timestamp) = path.into_inner();
+
+    let AppState {
+        signing_sk,
+        irma_server_host,
+        ..
+    } = state.get_ref().clone();
+
+    fetch_result(irma_server_host, token, timestamp).and_then(
+        move |r: SessionResult| -> Result<HttpResponse, crate::Error> {
+            let d = |status: KeyStatus| SigningKeyResponse { status, key: None };
+
+            let result = match r.status {
+                SessionStatus::Initialized => d(KeyStatus::Initialized),
+                SessionStatus::Connected => d(KeyStatus::Connected),
+                SessionStatus::Cancelled => d(KeyStatus::Cancelled),
+                SessionStatus::Timeout => d(KeyStatus::Timeout),
+                SessionStatus::Done => match fetch_identity(timestamp, &r.disclosed) {
+                    Some(i) => {
+                        let signing_sk = signing_sk.as_ref().ok_or(crate::Error::Unexpected)?;
+                        let mut rng = rand::thread_rng();
+                        let key = signing_sk.issue(&i, &mut rng).map_err(crate::Error::Core)?;
+
+                        SigningKeyResponse {
+                            status: KeyStatus::DoneValid,
+                            key: Some(key),
+                        }
+                    }
+                    None => d(KeyStatus::DoneInvalid),
+                },
+            };
+
+            Ok(HttpResponse::Ok().json(result))
+        },
+    )
+}
@@ -1,13 +1,26 @@
 use actix_rt::System;
 use clap::ArgMatches;
+use irmaseal_core::kem::{Kem, KiltzVahlisOne};
+use irmaseal_core::stream::MasterSigningKey;
+use irmaseal_core::SecretKey;
+use std::sync::Arc;
 
 use crate::handlers;
-use crate::util::{read_pk, read_sk};
+use crate::util::{read_pk, read_signing_sk, read_sk};
 
+/// The KEM for which the PKG extracts user secret keys.
+pub type PkgKem = KiltzVahlisOne;
+
+/// The state that is shared by all workers.
+///
+/// The master secret key and the master signing key are shared by reference,
+/// such that cloning the state does not copy them. The master signing key is only
+/// set when the PKG issues signing keys.
 #[derive(Clone)]
 pub struct AppState {
-    pub pk: ibe::kiltz_vahlis_one::PublicKey,
-    pub sk: ibe::kiltz_vahlis_one::SecretKey,
+    pub pk: <PkgKem as Kem>::PublicKey,
+    pub sk: Arc<SecretKey<PkgKem>>,
+    pub signing_sk: Option<Arc<MasterSigningKey>>,
     pub irma_server_host: String,
 }
 
@@ -17,12 +30,14 @@ pub fn exec(m: &ArgMatches) {
 
     let public = m.value_of("public").unwrap();
     let secret = m.value_of("secret").unwrap();
+    let signing_secret = m.value_of("signing-secret");
 
     let irma_server_host = m.value_of("irma").unwrap().to_string();
 
     let state = AppState {
         pk: read_pk(public).unwrap(),
-        sk: read_sk(secret).unwrap(),
+        sk: Arc::new(read_sk(secret).unwrap()),
+        signing_sk: signing_secret.map(|path| Arc::new(read_signing_sk(path).unwrap())),
         irma_server_host,
     };
 
@@ -45,6 +60,15 @@ pub fn exec(m: &ArgMatches) {
                 actix_web::web::resource("/v1/request/{token}/{timestamp}")
                     .route(actix_web::web::get().to_async(handlers::request_fetch)),
             )
+            .configure(|cfg| {
+                // Signing keys are only issued by a PKG that has a master signing key.
+                if state.signing_sk.is_some() {
+                    cfg.service(
+                        actix_web::web::resource("/v1/sign/{token}/{timestamp}")
+                            .route(actix_web::web::get().to_async(handlers::sign_fetch)),
+                    );
+                }
+            })
     })
     .bind(format!("{}:{}", host, port))
     .unwrap()
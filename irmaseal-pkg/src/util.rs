@@ -1,37 +1,24 @@
-use arrayref::array_ref;
-use ibe::kiltz_vahlis_one::{PublicKey, SecretKey};
-use irmaseal_core::Error;
+use irmaseal_core::kem::{Artifact, Kem};
+use irmaseal_core::stream::MasterSigningKey;
+use irmaseal_core::{Error, SecretKey};
+use zeroize::Zeroizing;
 
 use std::path::Path;
 
-pub fn read_pk(path: impl AsRef<Path>) -> Result<PublicKey, Error> {
-    const LENGTH: usize = 25056;
+use crate::server::PkgKem;
 
-    let bytes = std::fs::read(path).unwrap();
-    if bytes.len() != LENGTH {
-        return Err(Error::FormatViolation);
-    }
-
-    let bytes = array_ref![&bytes, 0, LENGTH];
-    Ok(open_ct(PublicKey::from_bytes(bytes)).ok_or(Error::FormatViolation)?)
+fn read_bytes(path: impl AsRef<Path>) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(std::fs::read(path).unwrap())
 }
 
-pub fn read_sk(path: impl AsRef<Path>) -> Result<SecretKey, Error> {
-    const LENGTH: usize = 48;
-
-    let bytes = std::fs::read(path).unwrap();
-    if bytes.len() != LENGTH {
-        return Err(Error::FormatViolation);
-    }
+pub fn read_pk(path: impl AsRef<Path>) -> Result<<PkgKem as Kem>::PublicKey, Error> {
+    Artifact::from_bytes(&read_bytes(path)).ok_or(Error::FormatViolation)
+}
 
-    let bytes = array_ref![&bytes, 0, LENGTH];
-    Ok(open_ct(SecretKey::from_bytes(bytes)).ok_or(Error::FormatViolation)?)
+pub fn read_sk(path: impl AsRef<Path>) -> Result<SecretKey<PkgKem>, Error> {
+    SecretKey::from_bytes(&read_bytes(path)).ok_or(Error::FormatViolation)
 }
 
-pub fn open_ct<T>(x: subtle::CtOption<T>) -> Option<T> {
-    if bool::from(x.is_some()) {
-        Some(x.unwrap())
-    } else {
-        None
-    }
+pub fn read_signing_sk(path: impl AsRef<Path>) -> Result<MasterSigningKey, Error> {
+    MasterSigningKey::from_bytes(&read_bytes(path)).ok_or(Error::FormatViolation)
 }